@@ -0,0 +1,140 @@
+use std::fmt;
+
+use crate::game::{Board, PieceType};
+use crate::moves::{Move, MoveError};
+use crate::square::Square;
+
+// Quick checks for obvious mistakes, for casual play modes that warn before
+// a move is played. These look at the opponent's replies only, so they catch
+// pieces left en prise and mates in one, not deeper tactics.
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Warning {
+    // The piece on this square can be taken for less than it is worth.
+    Hangs(PieceType, Square),
+    // The opponent can checkmate straight away.
+    AllowsMate,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::Hangs(ptype, square) => write!(f, "this hangs your {} on {}", piece_name(*ptype), square),
+            Warning::AllowsMate => write!(f, "this allows mate in one"),
+        }
+    }
+}
+
+fn piece_name(ptype: PieceType) -> &'static str {
+    match ptype {
+        PieceType::Pawn => "pawn",
+        PieceType::Rook => "rook",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    }
+}
+
+fn piece_value(ptype: PieceType) -> i32 {
+    match ptype {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 3,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 5,
+        PieceType::Queen => 9,
+        PieceType::King => 0,
+    }
+}
+
+// True if after the move the opponent can win material by capturing one of
+// the mover's pieces.
+pub fn is_hanging_after(board: &Board, mv: Move) -> Result<bool, MoveError> {
+    Ok(warns(board, mv)?.iter().any(|w| matches!(w, Warning::Hangs(_, _))))
+}
+
+// Everything worth warning about if the side to move plays this move. Hanging
+// pieces are listed most valuable first.
+pub fn warns(board: &Board, mv: Move) -> Result<Vec<Warning>, MoveError> {
+    let mut after = board.clone();
+    after.make_move(mv)?;
+    let us = board.side_to_move();
+
+    let mut warnings = Vec::new();
+    let mut hanging: Vec<(PieceType, Square)> = Vec::new();
+    let mut allows_mate = false;
+    for reply in after.legal_moves() {
+        let mut next = after.clone();
+        next.make_move_unchecked(reply);
+        if next.is_checkmate() {
+            allows_mate = true;
+        }
+        let target = match after.piece_at(reply.to) {
+            Some((ptype, color)) if color == us => ptype,
+            _ => continue,
+        };
+        let (attacker, _) = after.piece_at(reply.from).unwrap();
+        // If we can take back, the capture only wins the difference in value.
+        let gain = if next.is_square_attacked(reply.to, us) {
+            piece_value(target) - piece_value(attacker)
+        } else {
+            piece_value(target)
+        };
+        if gain > 0 && !hanging.contains(&(target, reply.to)) {
+            hanging.push((target, reply.to));
+        }
+    }
+    if allows_mate {
+        warnings.push(Warning::AllowsMate);
+    }
+    hanging.sort_by_key(|(ptype, _)| -piece_value(*ptype));
+    warnings.extend(hanging.into_iter().map(|(ptype, square)| Warning::Hangs(ptype, square)));
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    fn sq(name: &str) -> Square {
+        Square::new(name.as_bytes()[0] - b'a', b'8' - name.as_bytes()[1])
+    }
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move::new(sq(from), sq(to))
+    }
+
+    #[test]
+    fn test_hanging_queen() {
+        let b = board("4k3/8/8/2p5/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(is_hanging_after(&b, mv("d1", "d4")), Ok(true));
+        let warnings = warns(&b, mv("d1", "d4")).expect("");
+        assert_eq!(warnings, vec![Warning::Hangs(PieceType::Queen, sq("d4"))]);
+        assert_eq!(warnings[0].to_string(), "this hangs your queen on d4");
+        assert_eq!(is_hanging_after(&b, mv("d1", "d3")), Ok(false));
+    }
+
+    #[test]
+    fn test_defended_pieces() {
+        // Defended by the e3 pawn, but a pawn still wins the knight for itself.
+        let b = board("4k3/8/8/2p5/8/1N2P3/8/4K3 w - - 0 1");
+        assert_eq!(is_hanging_after(&b, mv("b3", "d4")), Ok(true));
+        // Only the queen attacks d4, and taking a defended knight loses it.
+        let b = board("3k4/8/8/8/7q/1N2P3/8/K7 w - - 0 1");
+        assert_eq!(is_hanging_after(&b, mv("b3", "d4")), Ok(false));
+        assert_eq!(is_hanging_after(&b, mv("b3", "c5")), Ok(false));
+        assert_eq!(is_hanging_after(&b, mv("e3", "e4")), Ok(true));
+        assert_eq!(is_hanging_after(&b, mv("e3", "d4")), Err(MoveError::Illegal));
+    }
+
+    #[test]
+    fn test_allows_back_rank_mate() {
+        let b = board("3r2k1/8/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        assert_eq!(warns(&b, mv("a1", "a2")), Ok(vec![Warning::AllowsMate]));
+        assert_eq!(warns(&b, mv("h2", "h3")), Ok(vec![]));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::game::Board;
+use crate::moves::Move;
+
+// Position analysis that works by trying every line rather than by
+// evaluating positions.
+
+fn is_mate_after(board: &Board, mv: Move) -> bool {
+    let mut after = board.clone();
+    after.make_move_unchecked(mv);
+    after.is_checkmate()
+}
+
+// Every move that checkmates straight away.
+pub fn find_mates_in_one(board: &Board) -> Vec<Move> {
+    board.legal_moves().into_iter().filter(|&mv| is_mate_after(board, mv)).collect()
+}
+
+fn has_mate_in_one(board: &Board) -> bool {
+    board.legal_moves().into_iter().any(|mv| is_mate_after(board, mv))
+}
+
+// Every move that forces mate on the next move whatever the reply. Moves
+// that mate at once are left out, since they are mates in one.
+pub fn find_mates_in_two(board: &Board) -> Vec<Move> {
+    board
+        .legal_moves()
+        .into_iter()
+        .filter(|&mv| {
+            let mut after = board.clone();
+            after.make_move_unchecked(mv);
+            let replies = after.legal_moves();
+            // No replies is mate or stalemate, neither of which is a mate in two.
+            !replies.is_empty()
+                && replies.into_iter().all(|reply| {
+                    let mut position = after.clone();
+                    position.make_move_unchecked(reply);
+                    has_mate_in_one(&position)
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    fn uci(moves: &[&str]) -> Vec<Move> {
+        moves.iter().map(|m| Move::from_uci(m).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_mates_in_one() {
+        // Back rank mate with either rook.
+        let mut mates = find_mates_in_one(&board("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1"));
+        mates.sort_by_key(|m| m.from);
+        assert_eq!(mates, uci(&["a1a8", "b1b8"]));
+        assert!(find_mates_in_one(&board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).is_empty());
+        // Promoting to a queen or rook mates, to a bishop or knight doesn't.
+        assert_eq!(find_mates_in_one(&board("7k/P7/6K1/8/8/8/8/8 w - - 0 1")), uci(&["a7a8q", "a7a8r"]));
+    }
+
+    #[test]
+    fn test_mates_in_two() {
+        // Kb6 leaves black only Kb8, and then Rh8 mates.
+        let position = board("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+        assert!(find_mates_in_one(&position).is_empty());
+        assert!(find_mates_in_two(&position).contains(&Move::from_uci("c6b6").unwrap()));
+        assert!(!find_mates_in_two(&position).contains(&Move::from_uci("h1h8").unwrap()));
+        assert!(find_mates_in_two(&board("k7/8/8/8/8/8/8/1R2K3 w - - 0 1")).is_empty());
+
+        // Qc7 is stalemate, not a mate in two.
+        let stalemate = board("k7/8/1Q6/8/8/8/8/6K1 w - - 0 1");
+        assert!(!find_mates_in_two(&stalemate).contains(&Move::from_uci("b6c7").unwrap()));
+    }
+}
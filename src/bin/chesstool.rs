@@ -0,0 +1,76 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+
+use chess_rust::pgn::{MoveNotation, PgnFormat, PgnReader};
+
+const USAGE: &str = "Usage: chesstool convert --to <san|long-algebraic|uci|figurine> [file]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+// Rewrites every game in the input with its moves in another notation. The
+// input can be in any of the notations. Games that can't be read are
+// reported and left out, and the number left out is returned.
+fn convert(notation: MoveNotation, input: Box<dyn Read>) -> io::Result<usize> {
+    let format = PgnFormat { notation, ..PgnFormat::default() };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut skipped = 0;
+    for (number, game) in PgnReader::new(input).enumerate() {
+        match game {
+            Ok(game) => writeln!(out, "{}", game.to_pgn_with(&format))?,
+            Err(e) => {
+                eprintln!("Skipping game {}: {}", number + 1, e);
+                skipped += 1;
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("convert") {
+        usage_error("Unknown command.");
+    }
+    let mut notation = None;
+    let mut path = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--to" {
+            let name = rest.next().unwrap_or_else(|| usage_error("--to needs a notation."));
+            notation = Some(name.parse().unwrap_or_else(|e: String| usage_error(&e)));
+        } else if path.is_none() {
+            path = Some(arg.clone());
+        } else {
+            usage_error(&format!("Unexpected argument \"{}\".", arg));
+        }
+    }
+    let notation = notation.unwrap_or_else(|| usage_error("Missing --to."));
+
+    let input: Box<dyn Read> = match path {
+        Some(path) => match File::open(&path) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                eprintln!("Can't open {}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdin()),
+    };
+    match convert(notation, input) {
+        Ok(0) => {}
+        Ok(skipped) => {
+            eprintln!("{} game(s) could not be converted.", skipped);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
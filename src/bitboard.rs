@@ -0,0 +1,384 @@
+mod magic;
+
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+use crate::game::PieceColor;
+use crate::square::{Direction, Square};
+
+// A set of squares, one bit per square. Bit n is set when the square with
+// index n is in the set (see Square for how squares are numbered).
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default, Hash)]
+pub struct Bitboard(pub u64);
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = 0x8080_8080_8080_8080;
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    pub const FULL: Bitboard = Bitboard(!0);
+    // a8 and h1 are light, a1 and h8 dark.
+    pub const LIGHT_SQUARES: Bitboard = Bitboard(0xaa55_aa55_aa55_aa55);
+    pub const DARK_SQUARES: Bitboard = Bitboard(!0xaa55_aa55_aa55_aa55);
+
+    pub fn from_square(square: Square) -> Bitboard {
+        Bitboard(1 << square.index())
+    }
+
+    pub fn contains(self, square: Square) -> bool {
+        self.0 & (1 << square.index()) != 0
+    }
+
+    pub fn set(&mut self, square: Square) {
+        self.0 |= 1 << square.index();
+    }
+
+    pub fn clear(&mut self, square: Square) {
+        self.0 &= !(1 << square.index());
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    // Returns the lowest set square without removing it.
+    pub fn lsb(self) -> Option<Square> {
+        if self.0 == 0 { None } else { Some(Square::from_index(self.0.trailing_zeros() as u8)) }
+    }
+
+    // Removes the lowest set square and returns it.
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    pub fn iter(self) -> BitboardIter {
+        BitboardIter(self)
+    }
+
+    // The shifts move every square one step in the given direction. Squares
+    // that would fall off the board are dropped rather than wrapping around
+    // to the other side. North is towards the 8th rank.
+    pub fn north(self) -> Bitboard {
+        Bitboard(self.0 >> 8)
+    }
+
+    pub fn south(self) -> Bitboard {
+        Bitboard(self.0 << 8)
+    }
+
+    pub fn east(self) -> Bitboard {
+        Bitboard((self.0 & !FILE_H) << 1)
+    }
+
+    pub fn west(self) -> Bitboard {
+        Bitboard((self.0 & !FILE_A) >> 1)
+    }
+
+    pub fn north_east(self) -> Bitboard {
+        Bitboard((self.0 & !FILE_H) >> 7)
+    }
+
+    pub fn north_west(self) -> Bitboard {
+        Bitboard((self.0 & !FILE_A) >> 9)
+    }
+
+    pub fn south_east(self) -> Bitboard {
+        Bitboard((self.0 & !FILE_H) << 9)
+    }
+
+    pub fn south_west(self) -> Bitboard {
+        Bitboard((self.0 & !FILE_A) << 7)
+    }
+
+    pub fn shift(self, dir: Direction) -> Bitboard {
+        match dir {
+            Direction::North => self.north(),
+            Direction::NorthEast => self.north_east(),
+            Direction::East => self.east(),
+            Direction::SouthEast => self.south_east(),
+            Direction::South => self.south(),
+            Direction::SouthWest => self.south_west(),
+            Direction::West => self.west(),
+            Direction::NorthWest => self.north_west(),
+        }
+    }
+}
+
+// The squares a piece jumping by one of these (dx, dy) steps attacks, for
+// each square it could stand on.
+const fn leaper_table(jumps: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [Bitboard(0); 64];
+    let mut index = 0;
+    while index < 64 {
+        let x = (index % 8) as i8;
+        let y = (index / 8) as i8;
+        let mut bits = 0;
+        let mut i = 0;
+        while i < jumps.len() {
+            let (to_x, to_y) = (x + jumps[i].0, y + jumps[i].1);
+            if to_x >= 0 && to_x < 8 && to_y >= 0 && to_y < 8 {
+                bits |= 1 << (to_y * 8 + to_x);
+            }
+            i += 1;
+        }
+        table[index] = Bitboard(bits);
+        index += 1;
+    }
+    table
+}
+
+const KNIGHT_ATTACKS: [Bitboard; 64] =
+    leaper_table(&[(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)]);
+const KING_ATTACKS: [Bitboard; 64] =
+    leaper_table(&[(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)]);
+
+const ROOK_DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+const BISHOP_DIRECTIONS: [Direction; 4] =
+    [Direction::NorthEast, Direction::SouthEast, Direction::SouthWest, Direction::NorthWest];
+
+// Attack sets: the squares a piece on `square` attacks. The sliding pieces
+// need the occupied squares, since they stop at the first piece in each
+// direction, which they attack whatever its color. Their attacks come from
+// magic bitboard tables, which are built the first time they are used.
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square.index()]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    KING_ATTACKS[square.index()]
+}
+
+// The two squares diagonally in front of a pawn of this color.
+pub fn pawn_attacks(square: Square, color: PieceColor) -> Bitboard {
+    let pawn = Bitboard::from_square(square);
+    match color {
+        PieceColor::White => pawn.north_east() | pawn.north_west(),
+        PieceColor::Black => pawn.south_east() | pawn.south_west(),
+    }
+}
+
+// Slider attacks found by walking out from the square one step at a time.
+// Used to build the magic tables.
+fn ray_attacks(square: Square, occupied: Bitboard, directions: &[Direction]) -> Bitboard {
+    let mut attacks = Bitboard::EMPTY;
+    for dir in directions {
+        let mut ray = Bitboard::from_square(square).shift(*dir);
+        while !ray.is_empty() {
+            attacks |= ray;
+            if !(ray & occupied).is_empty() {
+                break;
+            }
+            ray = ray.shift(*dir);
+        }
+    }
+    attacks
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    magic::rook_attacks(square, occupied)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    magic::bishop_attacks(square, occupied)
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+pub struct BitboardIter(Bitboard);
+
+impl Iterator for BitboardIter {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        self.0.pop_lsb()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.0.count() as usize;
+        (count, Some(count))
+    }
+}
+
+impl IntoIterator for Bitboard {
+    type Item = Square;
+    type IntoIter = BitboardIter;
+
+    fn into_iter(self) -> BitboardIter {
+        self.iter()
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 & rhs.0)
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(self.0 ^ rhs.0)
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, rhs: Bitboard) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, rhs: Bitboard) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, rhs: Bitboard) {
+        self.0 ^= rhs.0;
+    }
+}
+
+// Prints the set as an 8x8 grid with the 8th rank at the top, like Board::render.
+impl fmt::Display for Bitboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..8 {
+            for x in 0..8 {
+                write!(f, "{} ", if self.contains(Square::new(x, y)) { 'X' } else { '*' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_operations() {
+        let a = Bitboard::from_square(Square::new(0, 0)) | Bitboard::from_square(Square::new(1, 0));
+        let b = Bitboard::from_square(Square::new(1, 0)) | Bitboard::from_square(Square::new(2, 0));
+        assert_eq!((a & b).iter().collect::<Vec<_>>(), vec![Square::new(1, 0)]);
+        assert_eq!((a ^ b).count(), 2);
+        assert_eq!((a | b).count(), 3);
+        assert_eq!((!a).count(), 62);
+        assert!(!(!a).contains(Square::new(0, 0)));
+    }
+
+    #[test]
+    fn test_pop_lsb() {
+        let mut board = Bitboard::from_square(Square::new(3, 5)) | Bitboard::from_square(Square::new(6, 1));
+        assert_eq!(board.pop_lsb(), Some(Square::new(6, 1)));
+        assert_eq!(board.pop_lsb(), Some(Square::new(3, 5)));
+        assert_eq!(board.pop_lsb(), None);
+        assert!(board.is_empty());
+    }
+
+    #[test]
+    fn test_shifts_do_not_wrap() {
+        let h_file = Bitboard::from_square(Square::new(7, 4));
+        assert_eq!(h_file.east(), Bitboard::EMPTY);
+        assert_eq!(h_file.north_east(), Bitboard::EMPTY);
+        assert_eq!(h_file.west(), Bitboard::from_square(Square::new(6, 4)));
+        assert_eq!(h_file.north(), Bitboard::from_square(Square::new(7, 3)));
+        assert_eq!(h_file.south_west(), Bitboard::from_square(Square::new(6, 5)));
+
+        let a_file = Bitboard::from_square(Square::new(0, 0));
+        assert_eq!(a_file.west(), Bitboard::EMPTY);
+        assert_eq!(a_file.north(), Bitboard::EMPTY);
+        assert_eq!(a_file.south_east(), Bitboard::from_square(Square::new(1, 1)));
+    }
+
+    #[test]
+    fn test_shift_matches_offset() {
+        for dir in Direction::ALL.iter().copied() {
+            for index in 0..64 {
+                let square = Square::from_index(index);
+                let expected = match square.offset(dir) {
+                    None => Bitboard::EMPTY,
+                    Some(s) => Bitboard::from_square(s),
+                };
+                assert_eq!(Bitboard::from_square(square).shift(dir), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_square_colors() {
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            let light = square.color() == crate::square::SquareColor::Light;
+            assert_eq!(Bitboard::LIGHT_SQUARES.contains(square), light);
+            assert_eq!(Bitboard::DARK_SQUARES.contains(square), !light);
+        }
+    }
+
+    #[test]
+    fn test_leaper_attacks() {
+        let sq = |name: &str| name.parse::<Square>().unwrap();
+        assert_eq!(knight_attacks(sq("a1")).count(), 2);
+        assert!(knight_attacks(sq("a1")).contains(sq("b3")));
+        assert!(knight_attacks(sq("a1")).contains(sq("c2")));
+        assert_eq!(knight_attacks(sq("e4")).count(), 8);
+        assert_eq!(king_attacks(sq("h8")).count(), 3);
+        assert_eq!(king_attacks(sq("e4")).count(), 8);
+        let white = pawn_attacks(sq("a2"), PieceColor::White);
+        assert_eq!(white.iter().collect::<Vec<_>>(), vec![sq("b3")]);
+        let black = pawn_attacks(sq("e7"), PieceColor::Black);
+        assert!(black.contains(sq("d6")) && black.contains(sq("f6")) && black.count() == 2);
+    }
+
+    #[test]
+    fn test_slider_attacks() {
+        let sq = |name: &str| name.parse::<Square>().unwrap();
+        let occupied = Bitboard::from_square(sq("d6")) | Bitboard::from_square(sq("f4"));
+        let rook = rook_attacks(sq("d4"), occupied);
+        // Up the d file as far as the blocker on d6, which is attacked.
+        assert!(rook.contains(sq("d5")) && rook.contains(sq("d6")) && !rook.contains(sq("d7")));
+        assert!(rook.contains(sq("f4")) && !rook.contains(sq("g4")));
+        assert!(rook.contains(sq("a4")) && rook.contains(sq("d1")));
+        assert_eq!(rook.count(), 2 + 2 + 3 + 3);
+        assert_eq!(bishop_attacks(sq("a1"), Bitboard::EMPTY).count(), 7);
+        assert_eq!(queen_attacks(sq("d4"), occupied), rook | bishop_attacks(sq("d4"), occupied));
+    }
+
+    #[test]
+    fn test_display() {
+        let board = Bitboard::from_square(Square::new(0, 0)) | Bitboard::from_square(Square::new(7, 7));
+        let text = board.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], "X * * * * * * * ");
+        assert_eq!(lines[7], "* * * * * * * X ");
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::OnceLock;
+
+use crate::square::{Direction, Square};
+
+use super::{ray_attacks, Bitboard, BISHOP_DIRECTIONS, ROOK_DIRECTIONS};
+
+// Magic bitboards: lookup tables for the sliding pieces. Only the pieces on
+// a slider's lines matter to its attacks, and multiplying those bits by a
+// well chosen "magic" number packs them into the top bits of the product,
+// which then index a table of precomputed attack sets. The magics below
+// were found by trying sparse random numbers until one gave no clashing
+// indexes for any square; the tables are filled in the first time they are
+// needed.
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x1080_0040_0880_1020, 0x0840_0920_02c0_3000, 0x1900_2000_1040_0900, 0x0880_1000_0800_0480,
+    0x4200_1004_2008_0200, 0x8100_0201_0008_0400, 0x0200_0401_1088_6200, 0x0200_0080_4022_0411,
+    0x0404_8000_8440_0220, 0x0000_4010_0040_2000, 0x0086_0010_8122_0440, 0x0408_8008_0010_0280,
+    0x000a_0012_0104_0820, 0x8848_8002_0084_0080, 0x4001_0001_0004_0200, 0x0442_0001_0210_5084,
+    0x9080_0100_2080_4100, 0x0040_4040_0020_1009, 0x0000_8080_1000_2009, 0x2200_0900_21d0_0100,
+    0x0008_0080_0804_0080, 0x0004_0040_0201_0040, 0x0011_0400_0801_5042, 0x0000_0a00_0176_8104,
+    0x0000_8000_8020_4009, 0x2010_0041_4000_2001, 0x9800_2002_8010_0080, 0x1000_1000_8008_0080,
+    0x0442_000a_0004_9020, 0x2100_0400_8002_0080, 0x0800_1204_0090_0148, 0x0010_040a_0012_8541,
+    0x2800_8040_0080_0030, 0x1010_0020_0040_0041, 0x4000_2000_1100_4100, 0x0610_0084_1080_0800,
+    0x0400_8024_0280_0800, 0xc100_0200_8080_0400, 0x0002_0008_0200_0401, 0x0182_0858_8200_0401,
+    0x0220_2040_0080_8000, 0x2860_1000_4002_4022, 0x0001_0020_0411_0040, 0x9910_1042_000a_0020,
+    0x0004_0800_0400_8080, 0x0010_0400_0200_8080, 0x2012_0048_8102_0004, 0x8300_8424_4482_0011,
+    0x0088_4038_8201_0200, 0x0820_4000_8021_0100, 0x0110_9100_40a0_0300, 0x0801_1002_8008_0480,
+    0x0242_0090_0820_0600, 0x1002_0004_8950_0200, 0x0040_8002_0001_0080, 0x0091_8000_4100_0080,
+    0x0000_2093_0048_8001, 0x04c1_0024_1482_4001, 0x0200_2000_0b00_1041, 0x7000_1000_0420_0901,
+    0x8002_0020_0410_0802, 0x3001_0002_084c_0007, 0x0888_2218_0081_3004, 0x4000_0028_4084_0112,
+];
+
+const BISHOP_MAGICS: [u64; 64] = [
+    0xa010_0411_0800_3100, 0x0060_8202_0a00_2900, 0x6810_0106_1920_0000, 0x0828_1a05_2000_0408,
+    0x0001_1040_0100_0400, 0x0018_9010_0804_8400, 0x0004_0a02_1024_5280, 0x0002_0021_0808_a402,
+    0x9140_0484_1082_1200, 0x0800_0910_1082_0041, 0x2050_4804_8322_02c0, 0x0100_0914_0108_1000,
+    0x8021_0111_4000_0012, 0x0810_0208_0445_0400, 0x208b_0542_1090_08a2, 0x0080_084a_0804_0204,
+    0x0040_e2a8_0811_244c, 0x2505_0220_0800_8108, 0x0430_2201_0042_0040, 0x010a_0404_2022_0040,
+    0x1105_0002_9040_0000, 0x0093_0012_0082_2120, 0x4000_a620_4804_3004, 0x2801_2004_8a01_5004,
+    0x0060_9000_2a02_0814, 0x4404_2000_2408_00d0, 0x0110_2800_040a_4400, 0x1004_0800_8022_0040,
+    0x0001_0010_1100_4024, 0x0010_0440_0080_5040, 0x0914_0412_0082_0100, 0x0004_8210_1282_1480,
+    0x0024_0405_00c0_5021, 0x0088_6110_0208_0200, 0x0116_080a_0004_0020, 0x4000_0200_8008_0080,
+    0x2450_4501_4084_0040, 0x0000_8802_0148_4100, 0x0222_0204_0402_0092, 0x8081_1106_0000_2e00,
+    0x2842_1011_0500_0801, 0x1100_8090_0800_1025, 0x0002_0202_221c_0400, 0x0422_0140_2200_9020,
+    0x0210_0461_0210_0c00, 0xc004_0080_8202_9102, 0x00aa_4618_0110_1200, 0x0404_0800_8020_1108,
+    0x0205_4210_8c20_5002, 0x0410_5448_0410_0100, 0x0040_9108_4110_0000, 0x0400_2000_4202_1100,
+    0x0000_4204_8504_00c0, 0x0200_1004_10a4_2102, 0x1040_0208_0121_0102, 0x0805_0404_1042_0000,
+    0x2884_8041_3010_0200, 0x800c_2622_0124_2000, 0x1058_0001_9410_8800, 0x0014_2210_5442_0204,
+    0x0104_0000_12a0_2200, 0x0200_8810_0330_0100, 0x0140_4002_0284_0100, 0x0402_0208_0101_0201,
+];
+
+struct Magic {
+    // The squares whose occupancy affects the attacks: the slider's lines,
+    // without the edge squares at their ends, which are attacked whether
+    // or not anything stands on them.
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    // Where this square's attack sets start in Tables::attacks.
+    offset: usize,
+}
+
+struct Tables {
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+    attacks: Vec<Bitboard>,
+}
+
+static TABLES: OnceLock<Tables> = OnceLock::new();
+
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(Tables::new)
+}
+
+fn relevant_mask(square: Square, directions: &[Direction]) -> u64 {
+    let mut mask = 0;
+    for dir in directions {
+        let mut ray = Bitboard::from_square(square).shift(*dir);
+        loop {
+            let next = ray.shift(*dir);
+            if next.is_empty() {
+                break;
+            }
+            mask |= ray.0;
+            ray = next;
+        }
+    }
+    mask
+}
+
+// Fills in the attack sets for one square, for every occupancy of its mask,
+// at the end of `attacks`.
+fn build_square(square: Square, magic: u64, directions: &[Direction], attacks: &mut Vec<Bitboard>) -> Magic {
+    let mask = relevant_mask(square, directions);
+    let shift = 64 - mask.count_ones();
+    let offset = attacks.len();
+    attacks.resize(offset + (1 << mask.count_ones()), Bitboard::EMPTY);
+    // Steps through every subset of the mask.
+    let mut subset: u64 = 0;
+    loop {
+        let index = (subset.wrapping_mul(magic) >> shift) as usize;
+        attacks[offset + index] = ray_attacks(square, Bitboard(subset), directions);
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    Magic { mask, magic, shift, offset }
+}
+
+impl Tables {
+    fn new() -> Tables {
+        let mut attacks = Vec::new();
+        let mut build = |magics: &[u64; 64], directions: &[Direction]| -> Vec<Magic> {
+            (0..64)
+                .map(|index| build_square(Square::from_index(index as u8), magics[index], directions, &mut attacks))
+                .collect()
+        };
+        let rook = build(&ROOK_MAGICS, &ROOK_DIRECTIONS);
+        let bishop = build(&BISHOP_MAGICS, &BISHOP_DIRECTIONS);
+        Tables { rook, bishop, attacks }
+    }
+
+    fn lookup(&self, magic: &Magic, occupied: Bitboard) -> Bitboard {
+        let index = ((occupied.0 & magic.mask).wrapping_mul(magic.magic) >> magic.shift) as usize;
+        self.attacks[magic.offset + index]
+    }
+}
+
+pub(super) fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.lookup(&tables.rook[square.index()], occupied)
+}
+
+pub(super) fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.lookup(&tables.bishop[square.index()], occupied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tries every occupancy of every square's mask, so a magic that sends
+    // two occupancies with different attacks to the same entry is caught.
+    #[test]
+    fn test_magics_match_ray_walking() {
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            for (directions, attacks) in [
+                (ROOK_DIRECTIONS, rook_attacks as fn(Square, Bitboard) -> Bitboard),
+                (BISHOP_DIRECTIONS, bishop_attacks),
+            ].iter() {
+                let mask = relevant_mask(square, directions);
+                let mut subset: u64 = 0;
+                loop {
+                    // Pieces off the lines make no difference.
+                    let occupied = Bitboard(subset | (!mask & 0x1234_5678_9abc_def0));
+                    assert_eq!(attacks(square, occupied), ray_attacks(square, occupied, directions));
+                    subset = subset.wrapping_sub(mask) & mask;
+                    if subset == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_relevant_mask() {
+        // A rook in the corner sees six squares along each edge that matter.
+        assert_eq!(relevant_mask(Square::new(0, 0), &ROOK_DIRECTIONS).count_ones(), 12);
+        assert_eq!(relevant_mask(Square::new(3, 4), &ROOK_DIRECTIONS).count_ones(), 10);
+        assert_eq!(relevant_mask(Square::new(3, 4), &BISHOP_DIRECTIONS).count_ones(), 9);
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+
+use crate::game::PieceColor;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum CastlingSide {
+    King,
+    Queen,
+}
+
+// Which castling moves are still allowed. A right being set only means
+// neither the king nor that rook has moved yet, not that castling is legal
+// right now.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub struct CastlingRights(u8);
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const ALL: CastlingRights = CastlingRights(0b1111);
+
+    fn bit(color: PieceColor, side: CastlingSide) -> u8 {
+        match (color, side) {
+            (PieceColor::White, CastlingSide::King) => 1,
+            (PieceColor::White, CastlingSide::Queen) => 2,
+            (PieceColor::Black, CastlingSide::King) => 4,
+            (PieceColor::Black, CastlingSide::Queen) => 8,
+        }
+    }
+
+    pub fn has(self, color: PieceColor, side: CastlingSide) -> bool {
+        self.0 & CastlingRights::bit(color, side) != 0
+    }
+
+    pub fn grant(&mut self, color: PieceColor, side: CastlingSide) {
+        self.0 |= CastlingRights::bit(color, side);
+    }
+
+    pub fn remove(&mut self, color: PieceColor, side: CastlingSide) {
+        self.0 &= !CastlingRights::bit(color, side);
+    }
+
+    // Removes both rights for one color, e.g. after their king moves.
+    pub fn remove_color(&mut self, color: PieceColor) {
+        self.remove(color, CastlingSide::King);
+        self.remove(color, CastlingSide::Queen);
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    // The rights packed into the low four bits: white king side, white queen
+    // side, black king side, black queen side.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    // Parses the castling field of a FEN, e.g. "KQkq", "Kq" or "-".
+    pub fn from_fen(field: &str) -> Result<CastlingRights, String> {
+        let mut rights = CastlingRights::NONE;
+        if field == "-" {
+            return Ok(rights);
+        }
+        if field.is_empty() {
+            return Err(String::from("Invalid castling rights."));
+        }
+        for c in field.chars() {
+            let (color, side) = match c {
+                'K' => (PieceColor::White, CastlingSide::King),
+                'Q' => (PieceColor::White, CastlingSide::Queen),
+                'k' => (PieceColor::Black, CastlingSide::King),
+                'q' => (PieceColor::Black, CastlingSide::Queen),
+                _ => return Err(String::from("Invalid castling rights.")),
+            };
+            if rights.has(color, side) {
+                return Err(String::from("Invalid castling rights."));
+            }
+            rights.grant(color, side);
+        }
+        Ok(rights)
+    }
+}
+
+// Prints the rights the way they appear in a FEN.
+impl fmt::Display for CastlingRights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+        let flags = [
+            (PieceColor::White, CastlingSide::King, 'K'),
+            (PieceColor::White, CastlingSide::Queen, 'Q'),
+            (PieceColor::Black, CastlingSide::King, 'k'),
+            (PieceColor::Black, CastlingSide::Queen, 'q'),
+        ];
+        for (color, side, c) in flags.iter() {
+            if self.has(*color, *side) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_and_remove() {
+        let mut rights = CastlingRights::ALL;
+        rights.remove(PieceColor::White, CastlingSide::Queen);
+        assert!(!rights.has(PieceColor::White, CastlingSide::Queen));
+        assert!(rights.has(PieceColor::White, CastlingSide::King));
+        rights.remove_color(PieceColor::Black);
+        assert_eq!(rights.to_string(), "K");
+        rights.grant(PieceColor::Black, CastlingSide::Queen);
+        assert_eq!(rights.to_string(), "Kq");
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        for field in ["KQkq", "Kq", "k", "-"].iter() {
+            assert_eq!(CastlingRights::from_fen(field).expect("").to_string(), *field);
+        }
+        assert_eq!(CastlingRights::from_fen("qK").expect("").to_string(), "Kq");
+        assert!(CastlingRights::from_fen("KK").is_err());
+        assert!(CastlingRights::from_fen("KX").is_err());
+        assert!(CastlingRights::from_fen("").is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::game::PieceColor;
+
+// Chess clocks. The clock doesn't read the time itself: whoever runs the
+// game measures how long each move took and passes it in, so the same clock
+// works for live games, replays and tests.
+
+// Time given back to a player for each move.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Bonus {
+    None,
+    // Fischer increment: added after every move.
+    Increment(Duration),
+    // Bronstein delay: after a move, the time it took is given back, up to
+    // this much.
+    Bronstein(Duration),
+    // US or simple delay: the clock waits this long before it starts
+    // counting down.
+    Delay(Duration),
+}
+
+// One stage of a time control, e.g. the 90 minutes for the first 40 moves
+// of 40/90 + 30.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct Stage {
+    // The moves to make in this stage, or None for the rest of the game.
+    pub moves: Option<u32>,
+    // Added to the clock when the stage starts.
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TimeControl {
+    // A last stage with a move count is repeated, as in 40/120 repeating.
+    stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    // Panics if there are no stages.
+    pub fn new(stages: Vec<Stage>) -> TimeControl {
+        assert!(!stages.is_empty(), "A time control needs at least one stage.");
+        TimeControl { stages }
+    }
+
+    // The whole game in `time`, e.g. 90 minutes for classical.
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::new(vec![Stage { moves: None, time, bonus: Bonus::None }])
+    }
+
+    // The whole game in `time`, plus `increment` a move, e.g. 3+2 blitz.
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::new(vec![Stage { moves: None, time, bonus: Bonus::Increment(increment) }])
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    fn stage(&self, index: usize) -> &Stage {
+        &self.stages[index.min(self.stages.len() - 1)]
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ParseTimeControlError;
+
+impl fmt::Display for ParseTimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid time control.")
+    }
+}
+
+impl std::error::Error for ParseTimeControlError {}
+
+fn parse_seconds(text: &str) -> Result<Duration, ParseTimeControlError> {
+    text.parse::<u64>().map(Duration::from_secs).map_err(|_| ParseTimeControlError)
+}
+
+// The PGN TimeControl tag form: stages separated by colons, each "time",
+// "time+increment" or "moves/time", in seconds. 40/90 + 30 with a 30 second
+// increment is "40/5400+30:1800+30". Delays can't be written this way.
+impl FromStr for TimeControl {
+    type Err = ParseTimeControlError;
+
+    fn from_str(s: &str) -> Result<TimeControl, ParseTimeControlError> {
+        let mut stages = Vec::new();
+        for field in s.trim().split(':') {
+            let (moves, rest) = match field.find('/') {
+                Some(i) => {
+                    let moves = field[..i].parse::<u32>().map_err(|_| ParseTimeControlError)?;
+                    (Some(moves), &field[i + 1..])
+                }
+                None => (None, field),
+            };
+            let (time, bonus) = match rest.find('+') {
+                Some(i) => (parse_seconds(&rest[..i])?, Bonus::Increment(parse_seconds(&rest[i + 1..])?)),
+                None => (parse_seconds(rest)?, Bonus::None),
+            };
+            stages.push(Stage { moves, time, bonus });
+        }
+        Ok(TimeControl::new(stages))
+    }
+}
+
+fn index(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    // Each player's stage and the moves they have made in it.
+    stage: [usize; 2],
+    stage_moves: [u32; 2],
+    flagged: Option<PieceColor>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Clock {
+        let time = control.stage(0).time;
+        Clock { control, remaining: [time; 2], stage: [0; 2], stage_moves: [0; 2], flagged: None }
+    }
+
+    pub fn time_control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    // The time `color` has left, not counting any delay.
+    pub fn remaining(&self, color: PieceColor) -> Duration {
+        self.remaining[index(color)]
+    }
+
+    // Sets the time `color` has left, e.g. from the clock comments of a game
+    // being resumed.
+    pub fn set_remaining(&mut self, color: PieceColor, time: Duration) {
+        self.remaining[index(color)] = time;
+    }
+
+    // The player who ran out of time, if either has.
+    pub fn flagged(&self) -> Option<PieceColor> {
+        self.flagged
+    }
+
+    // Lets a player whose flag fell play on, e.g. when the game is taken
+    // back to before it ended. Their time is left at zero.
+    pub fn reset_flag(&mut self) {
+        self.flagged = None;
+    }
+
+    fn bonus(&self, color: PieceColor) -> Bonus {
+        self.control.stage(self.stage[index(color)]).bonus
+    }
+
+    // Whether `color` runs out of time by spending `elapsed` on their
+    // current move.
+    pub fn is_flagged(&self, color: PieceColor, elapsed: Duration) -> bool {
+        let allowed = match self.bonus(color) {
+            Bonus::Delay(delay) => self.remaining(color) + delay,
+            _ => self.remaining(color),
+        };
+        self.flagged == Some(color) || elapsed > allowed
+    }
+
+    // Takes a move that took `elapsed` off `color`'s clock and adds their
+    // bonus. Moving into the next stage adds its time. Returns false, and
+    // leaves the clock at zero, if the flag fell before the move was made.
+    pub fn record_move(&mut self, color: PieceColor, elapsed: Duration) -> bool {
+        if self.is_flagged(color, elapsed) {
+            self.remaining[index(color)] = Duration::from_secs(0);
+            self.flagged = Some(color);
+            return false;
+        }
+        let i = index(color);
+        let used = match self.bonus(color) {
+            Bonus::None | Bonus::Increment(_) => elapsed,
+            Bonus::Bronstein(delay) => elapsed - elapsed.min(delay),
+            Bonus::Delay(delay) => elapsed.saturating_sub(delay),
+        };
+        self.remaining[i] = self.remaining[i].saturating_sub(used);
+        if let Bonus::Increment(increment) = self.bonus(color) {
+            self.remaining[i] += increment;
+        }
+
+        self.stage_moves[i] += 1;
+        if self.control.stage(self.stage[i]).moves == Some(self.stage_moves[i]) {
+            self.stage[i] += 1;
+            self.stage_moves[i] = 0;
+            self.remaining[i] += self.control.stage(self.stage[i]).time;
+        }
+        true
+    }
+}
+
+// A clock reading in the "[%clk h:mm:ss]" form used in PGN comments, e.g.
+// 0:03:00. Fractions of a second are dropped.
+pub fn format_clk(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// Reads a clock reading written by format_clk, or one with fractions of a
+// second like 0:00:07.5.
+pub fn parse_clk(text: &str) -> Option<Duration> {
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let hours = parts[0].parse::<u64>().ok()?;
+    let minutes = parts[1].parse::<u64>().ok()?;
+    let seconds = parts[2].parse::<f64>().ok().filter(|s| *s >= 0.0)?;
+    Some(Duration::from_secs(hours * 3600 + minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_increment_and_stages() {
+        let control: TimeControl = "2/60+10:30".parse().unwrap();
+        assert_eq!(control.stages().len(), 2);
+        let mut clock = Clock::new(control);
+        assert!(clock.record_move(PieceColor::White, secs(20)));
+        assert_eq!(clock.remaining(PieceColor::White), secs(50));
+        assert_eq!(clock.remaining(PieceColor::Black), secs(60));
+        // The second move finishes the first stage and brings 30 more seconds.
+        assert!(clock.record_move(PieceColor::White, secs(20)));
+        assert_eq!(clock.remaining(PieceColor::White), secs(70));
+        // The last stage has no increment.
+        assert!(clock.record_move(PieceColor::White, secs(20)));
+        assert_eq!(clock.remaining(PieceColor::White), secs(50));
+
+        assert!(!clock.record_move(PieceColor::White, secs(51)));
+        assert_eq!(clock.flagged(), Some(PieceColor::White));
+        assert_eq!(clock.remaining(PieceColor::White), secs(0));
+        assert_eq!("40/5400+30:1800+30".parse::<TimeControl>().unwrap().stages()[1].moves, None);
+        assert!("40/".parse::<TimeControl>().is_err());
+        assert!("-".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_delays() {
+        let stage = |bonus| TimeControl::new(vec![Stage { moves: None, time: secs(60), bonus }]);
+        // A Bronstein delay gives back up to 5 seconds of the time used.
+        let mut clock = Clock::new(stage(Bonus::Bronstein(secs(5))));
+        clock.record_move(PieceColor::Black, secs(3));
+        assert_eq!(clock.remaining(PieceColor::Black), secs(60));
+        clock.record_move(PieceColor::Black, secs(8));
+        assert_eq!(clock.remaining(PieceColor::Black), secs(57));
+        assert!(clock.is_flagged(PieceColor::Black, secs(58)));
+
+        // A simple delay holds the clock for the first 5 seconds, so a move
+        // can take 5 seconds more than what is left.
+        let mut clock = Clock::new(stage(Bonus::Delay(secs(5))));
+        clock.record_move(PieceColor::Black, secs(8));
+        assert_eq!(clock.remaining(PieceColor::Black), secs(57));
+        assert!(!clock.is_flagged(PieceColor::Black, secs(62)));
+        assert!(clock.is_flagged(PieceColor::Black, secs(63)));
+    }
+
+    #[test]
+    fn test_clk() {
+        assert_eq!(format_clk(secs(3 * 3600 + 5 * 60 + 7)), "3:05:07");
+        assert_eq!(parse_clk("0:03:00"), Some(secs(180)));
+        assert_eq!(parse_clk("0:00:07.5"), Some(Duration::from_millis(7500)));
+        assert_eq!(parse_clk("3:00"), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt;
+
+use crate::game::{Board, FenError};
+use crate::moves::Move;
+use crate::san::SanError;
+
+// Extended Position Description, the format test suites like WAC and STS come
+// in. A record is the first four FEN fields followed by operations, each an
+// opcode with operands and ended by a semicolon, e.g.
+// 2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum EpdError {
+    // Fewer than the four position fields.
+    MissingField,
+    BadPosition(FenError),
+    // An operation without an opcode, a string without its closing quote,
+    // or a bad hmvc or fmvn value.
+    BadOperation,
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::MissingField => write!(f, "EPD record is missing position fields."),
+            EpdError::BadPosition(e) => write!(f, "Invalid EPD position: {}", e),
+            EpdError::BadOperation => write!(f, "Invalid EPD operation."),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Epd {
+    pub board: Board,
+    // Opcodes with their operands, in the order they were written. The move
+    // clocks are taken out into the board rather than kept here.
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+// Opcodes whose operands are free text and always written in quotes.
+fn is_string_opcode(opcode: &str) -> bool {
+    opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit())
+}
+
+// Splits the operations part of a record into opcodes and operands.
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => {}
+            ';' => {
+                if words.is_empty() {
+                    return Err(EpdError::BadOperation);
+                }
+                let opcode = words.remove(0);
+                operations.push((opcode, std::mem::take(&mut words)));
+            }
+            '"' => {
+                // A quoted operand can't be the opcode.
+                if words.is_empty() {
+                    return Err(EpdError::BadOperation);
+                }
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(EpdError::BadOperation),
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                    }
+                }
+                words.push(word);
+            }
+            _ => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ';' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                words.push(word);
+            }
+        }
+    }
+    // The semicolon after the last operation is often left out.
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+    Ok(operations)
+}
+
+impl Epd {
+    pub fn parse(record: &str) -> Result<Epd, EpdError> {
+        let mut rest = record.trim();
+        let mut fields = Vec::new();
+        for _ in 0..4 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(EpdError::MissingField);
+            }
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+
+        let mut operations = parse_operations(rest)?;
+        let mut clock = |opcode: &str, default: &str| -> Result<String, EpdError> {
+            match operations.iter().position(|(o, _)| o == opcode) {
+                None => Ok(String::from(default)),
+                Some(i) => {
+                    let (_, operands) = operations.remove(i);
+                    match operands.as_slice() {
+                        [n] if n.parse::<u32>().is_ok() => Ok(n.clone()),
+                        _ => Err(EpdError::BadOperation),
+                    }
+                }
+            }
+        };
+        let halfmove = clock("hmvc", "0")?;
+        let fullmove = clock("fmvn", "1")?;
+
+        let fen = format!("{} {} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], halfmove, fullmove);
+        let board = Board::new_from_fen(fen).map_err(EpdError::BadPosition)?;
+        Ok(Epd { board, operations })
+    }
+
+    // The operands of an opcode, if the record has it.
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(o, _)| o == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    // Adds an operation, replacing any earlier one with the same opcode.
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(o, _)| o == opcode) {
+            Some(operation) => operation.1 = operands,
+            None => self.operations.push((String::from(opcode), operands)),
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id").and_then(|operands| operands.first()).map(|s| s.as_str())
+    }
+
+    // The "bm" moves, the best moves the suite expects. Empty if there
+    // aren't any.
+    pub fn best_moves(&self) -> Result<Vec<Move>, SanError> {
+        self.san_operands("bm")
+    }
+
+    // The "am" moves, ones that should be avoided.
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, SanError> {
+        self.san_operands("am")
+    }
+
+    fn san_operands(&self, opcode: &str) -> Result<Vec<Move>, SanError> {
+        self.operation(opcode).unwrap_or(&[]).iter().map(|san| self.board.move_from_san(san)).collect()
+    }
+
+    // The "ce" centipawn evaluation, from the side to move's point of view.
+    pub fn centipawn_eval(&self) -> Option<i32> {
+        self.operation("ce").and_then(|operands| operands.first()).and_then(|s| s.parse().ok())
+    }
+
+    // Writes the record back out. The move clocks are included as hmvc and
+    // fmvn only when they differ from a fresh position's 0 and 1.
+    pub fn to_epd(&self) -> String {
+        let fen = self.board.to_fen();
+        let mut epd = fen.split(' ').take(4).collect::<Vec<_>>().join(" ");
+        let mut operations = self.operations.clone();
+        if self.board.halfmove_clock() != 0 {
+            operations.push((String::from("hmvc"), vec![self.board.halfmove_clock().to_string()]));
+        }
+        if self.board.fullmove_number() != 1 {
+            operations.push((String::from("fmvn"), vec![self.board.fullmove_number().to_string()]));
+        }
+        for (opcode, operands) in &operations {
+            epd.push(' ');
+            epd.push_str(opcode);
+            for operand in operands {
+                let quote = is_string_opcode(opcode)
+                    || operand.is_empty()
+                    || operand.contains(char::is_whitespace)
+                    || operand.contains(';');
+                if quote {
+                    epd.push_str(&format!(" \"{}\"", operand));
+                } else {
+                    epd.push(' ');
+                    epd.push_str(operand);
+                }
+            }
+            epd.push(';');
+        }
+        epd
+    }
+}
+
+impl fmt::Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_epd())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAC_1: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn test_parse() {
+        let epd = Epd::parse(WAC_1).expect("");
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.best_moves(), Ok(vec![Move::from_uci("g3g6").unwrap()]));
+        assert_eq!(epd.avoid_moves(), Ok(vec![]));
+        assert_eq!(epd.centipawn_eval(), None);
+        assert_eq!(epd.board.fullmove_number(), 1);
+        assert_eq!(epd.to_epd(), WAC_1);
+
+        let sts = "1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; id \"STS(v1.0) Undermine.001\"; \
+                   c0 \"f5=10, Be5+=2, Bf2=3, Bg4=2\"; ce -35; hmvc 3; fmvn 40";
+        let epd = Epd::parse(sts).expect("");
+        assert_eq!(epd.id(), Some("STS(v1.0) Undermine.001"));
+        assert_eq!(epd.operation("c0"), Some(&[String::from("f5=10, Be5+=2, Bf2=3, Bg4=2")][..]));
+        assert_eq!(epd.centipawn_eval(), Some(-35));
+        assert_eq!(epd.best_moves(), Ok(vec![Move::from_uci("f4f5").unwrap()]));
+        assert_eq!(epd.board.halfmove_clock(), 3);
+        assert_eq!(epd.board.fullmove_number(), 40);
+        assert_eq!(epd.operation("hmvc"), None);
+        assert_eq!(Epd::parse(&epd.to_epd()), Ok(epd));
+    }
+
+    #[test]
+    fn test_set_operation() {
+        let mut epd = Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q -").expect("");
+        assert!(epd.operations.is_empty());
+        epd.set_operation("am", vec![String::from("Ra8+")]);
+        epd.set_operation("id", vec![String::from("test 1")]);
+        epd.set_operation("am", vec![String::from("Kd1"), String::from("Kf1")]);
+        assert_eq!(epd.to_epd(), "4k3/8/8/8/8/8/8/R3K3 w Q - am Kd1 Kf1; id \"test 1\";");
+        assert_eq!(epd.avoid_moves().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q"), Err(EpdError::MissingField));
+        assert_eq!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - id \"open"), Err(EpdError::BadOperation));
+        assert_eq!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - ; bm Ra8"), Err(EpdError::BadOperation));
+        assert_eq!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - hmvc x;"), Err(EpdError::BadOperation));
+        assert_eq!(Epd::parse("8/8/8/8/8/8/8/R3K3 w Q -"), Err(EpdError::BadPosition(FenError::MissingKing)));
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - bm Rb8;").expect("");
+        assert_eq!(epd.best_moves(), Err(SanError::Illegal));
+    }
+}
@@ -0,0 +1,297 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+
+use crate::game::PieceColor;
+
+// Scores for positions, and turning centipawn scores into chances of winning.
+//
+// Unless a function says otherwise, scores in this crate are relative to the
+// side to move: positive is good for whoever is about to play. Use
+// Score::to_white and Score::from_white to convert to and from the White
+// relative scores that PGN annotations and most GUIs show.
+
+// How good a position is for one side: either a centipawn advantage or a
+// forced mate. Mates are counted in full moves, like UCI does, so MateIn(1)
+// means the side can mate with its next move and MatedIn(1) means it will be
+// mated on the opponent's next move.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum Score {
+    // Anything past the range Score::cp clamps to is treated as clamped when
+    // the score is negated, compared or written out.
+    Cp(i32),
+    MateIn(u32),
+    MatedIn(u32),
+}
+
+// Search code often packs scores into one integer, with a mate found `p`
+// plies from the root scored MATE_VALUE - p. Centipawn scores stay below
+// MATE_VALUE - MAX_MATE_PLY so the two ranges never overlap.
+pub const MATE_VALUE: i32 = 32_000;
+pub const MAX_MATE_PLY: i32 = 1_000;
+const MAX_CP: i32 = MATE_VALUE - MAX_MATE_PLY;
+
+impl Score {
+    // A centipawn score, clamped so it can't be mistaken for a mate.
+    pub fn cp(value: i32) -> Score {
+        Score::Cp(value.clamp(-MAX_CP, MAX_CP))
+    }
+
+    // Builds a score from the packed integer form described at MATE_VALUE.
+    pub fn from_value(value: i32) -> Score {
+        if value > MAX_CP {
+            let plies = (MATE_VALUE - value).max(1);
+            Score::MateIn(((plies + 1) / 2) as u32)
+        } else if value < -MAX_CP {
+            let plies = (MATE_VALUE + value).max(1);
+            Score::MatedIn(((plies + 1) / 2) as u32)
+        } else {
+            Score::Cp(value)
+        }
+    }
+
+    // The packed integer form of the score. Mates become the shortest
+    // number of plies that fits the move count.
+    pub fn value(self) -> i32 {
+        match self {
+            Score::Cp(cp) => cp.clamp(-MAX_CP, MAX_CP),
+            Score::MateIn(n) => MATE_VALUE - (2 * n.min(MAX_MATE_PLY as u32 / 2) as i32 - 1).max(1),
+            Score::MatedIn(n) => -MATE_VALUE + 2 * n.min(MAX_MATE_PLY as u32 / 2) as i32,
+        }
+    }
+
+    // Converts a side to move relative score into a White relative one.
+    pub fn to_white(self, side_to_move: PieceColor) -> Score {
+        if side_to_move == PieceColor::White { self } else { -self }
+    }
+
+    // Converts a White relative score into one relative to the side to move.
+    pub fn from_white(self, side_to_move: PieceColor) -> Score {
+        // Flipping the sign is its own inverse, so this is the same as to_white.
+        self.to_white(side_to_move)
+    }
+
+    pub fn is_mate(self) -> bool {
+        !matches!(self, Score::Cp(_))
+    }
+
+    // The score as it appears in a UCI info line, e.g. "score cp 35" or
+    // "score mate -3".
+    pub fn to_uci(self) -> String {
+        match self {
+            Score::Cp(_) => format!("score cp {}", self.value()),
+            Score::MateIn(n) => format!("score mate {}", n),
+            Score::MatedIn(n) => format!("score mate -{}", n),
+        }
+    }
+
+    // Orders scores from worst to best: being mated sooner is worse, then
+    // centipawns, then mating sooner is better.
+    fn sort_key(self) -> (u8, i64) {
+        match self {
+            Score::MatedIn(n) => (0, n as i64),
+            Score::Cp(_) => (1, self.value() as i64),
+            Score::MateIn(n) => (2, -(n as i64)),
+        }
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Score) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Score) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// The same score from the other side's point of view.
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        match self {
+            Score::Cp(_) => Score::Cp(-self.value()),
+            Score::MateIn(n) => Score::MatedIn(n),
+            Score::MatedIn(n) => Score::MateIn(n),
+        }
+    }
+}
+
+// Human readable form: "+0.35", "-1.20", "#5" or "#-3".
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Cp(_) => {
+                let cp = self.value();
+                write!(f, "{}{}.{:02}", if cp < 0 { "-" } else { "+" }, cp.abs() / 100, cp.abs() % 100)
+            }
+            Score::MateIn(n) => write!(f, "#{}", n),
+            Score::MatedIn(n) => write!(f, "#-{}", n),
+        }
+    }
+}
+
+// The chances of a win, draw and loss, in parts per thousand. They always
+// add up to 1000.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct Wdl {
+    pub win: u32,
+    pub draw: u32,
+    pub loss: u32,
+}
+
+impl Wdl {
+    // The same chances from the other side's point of view.
+    pub fn flipped(self) -> Wdl {
+        Wdl { win: self.loss, draw: self.draw, loss: self.win }
+    }
+}
+
+// A logistic model mapping a centipawn advantage to win/draw/loss chances.
+// The chance of winning is 1 / (1 + e^(-k * (cp - draw_margin))) and the
+// chance of losing is the same with cp negated; whatever is left is a draw.
+// Stronger players convert advantages more reliably, so k grows with rating:
+// k = slope * e^((elo - reference_elo) / elo_scale).
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct WinProbabilityModel {
+    pub slope: f64,
+    pub reference_elo: f64,
+    pub elo_scale: f64,
+    pub draw_margin: f64,
+}
+
+impl Default for WinProbabilityModel {
+    // The slope at the reference rating is the one lichess uses for its
+    // "winning chances" graph.
+    fn default() -> WinProbabilityModel {
+        WinProbabilityModel {
+            slope: 0.003_682_08,
+            reference_elo: 2000.0,
+            elo_scale: 1500.0,
+            draw_margin: 100.0,
+        }
+    }
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+impl WinProbabilityModel {
+    fn k(&self, elo: f64) -> f64 {
+        self.slope * ((elo - self.reference_elo) / self.elo_scale).exp()
+    }
+
+    // Win, draw and loss chances for the side the `cp` score is relative to,
+    // with both players rated `elo`. Pass a side to move relative score to get
+    // the side to move's chances, or a White relative one for White's.
+    pub fn wdl(&self, cp: i32, elo: u32) -> Wdl {
+        let k = self.k(elo as f64);
+        let cp = cp as f64;
+        let win = (logistic(k * (cp - self.draw_margin)) * 1000.0).round() as u32;
+        let loss = (logistic(k * (-cp - self.draw_margin)) * 1000.0).round() as u32;
+        let loss = loss.min(1000 - win);
+        Wdl { win, draw: 1000 - win - loss, loss }
+    }
+
+    // The expected score (a win counting 1 and a draw 0.5), between 0 and 1,
+    // for the side the `cp` score is relative to.
+    pub fn win_probability(&self, cp: i32, elo: u32) -> f64 {
+        let k = self.k(elo as f64);
+        let cp = cp as f64;
+        let win = logistic(k * (cp - self.draw_margin));
+        let loss = logistic(k * (-cp - self.draw_margin));
+        win + (1.0 - win - loss) / 2.0
+    }
+}
+
+// win_probability using the default model.
+pub fn win_probability(cp: i32, elo: u32) -> f64 {
+    WinProbabilityModel::default().win_probability(cp, elo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_ordering() {
+        let mut scores = vec![Score::Cp(50), Score::MateIn(1), Score::MatedIn(5), Score::Cp(-300),
+                              Score::MateIn(4), Score::MatedIn(1), Score::Cp(0)];
+        scores.sort();
+        assert_eq!(scores, vec![Score::MatedIn(1), Score::MatedIn(5), Score::Cp(-300), Score::Cp(0),
+                                Score::Cp(50), Score::MateIn(4), Score::MateIn(1)]);
+        assert_eq!(-Score::MateIn(3), Score::MatedIn(3));
+        assert_eq!(-Score::Cp(25), Score::Cp(-25));
+    }
+
+    #[test]
+    fn test_perspective() {
+        assert_eq!(Score::Cp(40).to_white(PieceColor::White), Score::Cp(40));
+        assert_eq!(Score::Cp(40).to_white(PieceColor::Black), Score::Cp(-40));
+        assert_eq!(Score::MateIn(2).to_white(PieceColor::Black), Score::MatedIn(2));
+        assert_eq!(Score::Cp(-75).from_white(PieceColor::Black), Score::Cp(75));
+        let wdl = Wdl { win: 600, draw: 300, loss: 100 };
+        assert_eq!(wdl.flipped(), Wdl { win: 100, draw: 300, loss: 600 });
+        let model = WinProbabilityModel::default();
+        assert_eq!(model.wdl(-250, 2000), model.wdl(250, 2000).flipped());
+    }
+
+    #[test]
+    fn test_score_packing() {
+        assert_eq!(Score::from_value(MATE_VALUE - 1), Score::MateIn(1));
+        assert_eq!(Score::from_value(MATE_VALUE - 5), Score::MateIn(3));
+        assert_eq!(Score::from_value(-MATE_VALUE + 2), Score::MatedIn(1));
+        assert_eq!(Score::from_value(-MATE_VALUE + 6), Score::MatedIn(3));
+        assert_eq!(Score::from_value(123), Score::Cp(123));
+        for score in [Score::MateIn(1), Score::MateIn(7), Score::MatedIn(2), Score::Cp(-40)].iter() {
+            assert_eq!(Score::from_value(score.value()), *score);
+        }
+        assert_eq!(Score::cp(i32::MAX), Score::Cp(MATE_VALUE - MAX_MATE_PLY));
+        assert!(!Score::cp(i32::MIN).is_mate());
+
+        // Centipawns built directly, out of that range, act as if clamped.
+        assert_eq!(-Score::Cp(i32::MIN), Score::Cp(MAX_CP));
+        assert_eq!(Score::Cp(i32::MIN).to_white(PieceColor::Black), Score::Cp(MAX_CP));
+        assert_eq!(Score::Cp(i32::MIN).to_uci(), "score cp -31000");
+        assert_eq!(Score::Cp(i32::MIN).to_string(), "-310.00");
+        assert_eq!(Score::Cp(31_500).cmp(&Score::Cp(MAX_CP)), Ordering::Equal);
+        assert!(Score::Cp(i32::MAX) < Score::MateIn(50));
+    }
+
+    #[test]
+    fn test_score_formatting() {
+        assert_eq!(Score::Cp(35).to_uci(), "score cp 35");
+        assert_eq!(Score::MateIn(5).to_uci(), "score mate 5");
+        assert_eq!(Score::MatedIn(3).to_uci(), "score mate -3");
+        assert_eq!(Score::Cp(35).to_string(), "+0.35");
+        assert_eq!(Score::Cp(-120).to_string(), "-1.20");
+        assert_eq!(Score::MatedIn(2).to_string(), "#-2");
+    }
+
+    #[test]
+    fn test_win_probability() {
+        assert!((win_probability(0, 1500) - 0.5).abs() < 1e-9);
+        assert!((win_probability(150, 1500) + win_probability(-150, 1500) - 1.0).abs() < 1e-9);
+        assert!(win_probability(100, 1500) < win_probability(300, 1500));
+        assert!(win_probability(300, 1500) < win_probability(300, 2500));
+        assert!(win_probability(2000, 2000) > 0.99);
+    }
+
+    #[test]
+    fn test_wdl() {
+        let model = WinProbabilityModel::default();
+        for cp in [-1000, -200, 0, 35, 400, 5000].iter() {
+            let wdl = model.wdl(*cp, 2000);
+            assert_eq!(wdl.win + wdl.draw + wdl.loss, 1000);
+        }
+        let even = model.wdl(0, 2000);
+        assert_eq!(even.win, even.loss);
+        assert!(model.wdl(0, 2800).draw > even.draw);
+        assert!(model.wdl(500, 2000).win > 800);
+    }
+}
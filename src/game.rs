@@ -1,6 +1,24 @@
+mod attacks;
+mod make_move;
+mod movegen;
+mod perft;
+mod zobrist;
 
-#[derive(PartialEq, Debug, Copy, Clone)]
-enum PieceType {
+use std::fmt;
+
+pub use make_move::UndoInfo;
+
+use crate::bitboard::Bitboard;
+use crate::castling::{CastlingRights, CastlingSide};
+use crate::moves::Move;
+use crate::position_id::{Fnv, PositionId};
+use crate::square::{Square, SquareColor};
+
+// The position every normal game starts from.
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum PieceType {
     Pawn,
     Rook,
     Knight,
@@ -9,37 +27,44 @@ enum PieceType {
     King,
 }
 
-#[derive(PartialEq, Debug, Copy, Clone)]
-enum PieceColor {
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum PieceColor {
     Black,
     White,
 }
 
-#[derive(Debug, PartialEq)]
-struct Piece {
-    x: u32,
-    y: u32,
-    ptype: PieceType,
-    color: PieceColor,
+impl PieceColor {
+    pub fn opposite(self) -> PieceColor {
+        match self {
+            PieceColor::Black => PieceColor::White,
+            PieceColor::White => PieceColor::Black,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+type Mailbox = [Option<(PieceType, PieceColor)>; 64];
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Board {
-    pieces: Vec<Piece>,
+    // The squares holding each type of piece, indexed by `PieceType as
+    // usize`, and each side's pieces, indexed by `PieceColor as usize`.
+    by_type: [Bitboard; 6],
+    by_color: [Bitboard; 2],
+
+    // The same pieces indexed by square (see Square for the numbering) so
+    // looking up what is on a square doesn't need a search through the
+    // bitboards. Must be updated whenever they change.
+    mailbox: Mailbox,
 
     // Who's move is it
     current_move: PieceColor,
 
-    // These are true if the piece may be able to castle in the future,
-    // and false if they can no longer castle.
-    can_white_king_castle: bool,
-    can_white_queen_castle: bool,
-    can_black_king_castle: bool,
-    can_black_queen_castle: bool,
+    // Which sides may still be able to castle in the future.
+    castling_rights: CastlingRights,
 
     // If a pawn has just moved two squares the square behind it's current position
     // goes in this variable. This is used for en passant.
-    en_passant_square: Option<(u8, u8)>,
+    en_passant_square: Option<Square>,
 
     // Number of halfmoves since the last capture (a halfmove is everytime one person plays)
     halfmove_clock: u8,
@@ -47,6 +72,10 @@ pub struct Board {
     // Number of fullmoves since the start of the game.
     // Starts at one and is incremented after black plays.
     fullmove_num: u32,
+
+    // The Zobrist hash of the position (see zobrist_hash). Updated along with
+    // the pieces and state it covers.
+    zobrist: u64,
 }
 
 fn char_to_piece_type(c: char) -> Result<PieceType, String> {
@@ -72,85 +101,456 @@ fn piece_type_to_char(c: PieceType) -> char {
     }
 }
 
-fn square_from_string(s: String) -> Result<(u8, u8), String> {
-    if s.len() != 2 { return Err(String::from("Invalid string to turn into square.")); }
-    Ok((
-        (s.as_bytes()[0] as u32 - 'a' as u32) as u8,
-        match (s.as_bytes()[1] as char).to_digit(10) {
-            None => return Err(String::from("Invalid string to turn into square.")),
-            Some(n) => (n - 1) as u8,
-        }
-    ))
+// Why new_from_fen rejected a FEN.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum FenError {
+    // Fewer than the six space separated fields.
+    MissingField,
+    TooManyFields,
+    // The piece placement has an unknown character or a rank that isn't
+    // exactly eight squares, or doesn't have eight ranks.
+    BadPiecePlacement,
+    BadSideToMove,
+    // Not a valid castling field, or grants castling to a king or rook that
+    // isn't on its starting square.
+    BadCastlingField,
+    // Not a square, or not one a pawn could just have skipped over.
+    BadEnPassantSquare,
+    BadHalfmoveClock,
+    BadFullmoveNumber,
+    MissingKing,
+    TooManyKings,
+    // More than 16 pieces or 8 pawns on one side.
+    TooManyPieces,
+    PawnOnBackRank,
+    // The side that isn't to move is in check, which can't happen in a game.
+    OpponentInCheck,
 }
 
-impl Piece {
-
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            FenError::MissingField => "FEN is missing fields.",
+            FenError::TooManyFields => "FEN has too many fields.",
+            FenError::BadPiecePlacement => "Invalid piece placement in FEN.",
+            FenError::BadSideToMove => "Invalid side to move in FEN.",
+            FenError::BadCastlingField => "Invalid castling rights in FEN.",
+            FenError::BadEnPassantSquare => "Invalid en passant square in FEN.",
+            FenError::BadHalfmoveClock => "Invalid halfmove clock in FEN.",
+            FenError::BadFullmoveNumber => "Invalid fullmove number in FEN.",
+            FenError::MissingKing => "Each side must have a king.",
+            FenError::TooManyKings => "Each side can only have one king.",
+            FenError::TooManyPieces => "A side has more pieces than it could have.",
+            FenError::PawnOnBackRank => "Pawns can't be on the first or last rank.",
+            FenError::OpponentInCheck => "The side not to move is in check.",
+        };
+        write!(f, "{}", message)
+    }
 }
 
-impl Board {
-    // Makes a new board from a FEN notation.
-    pub fn new_from_fen(fen: String) -> Result<Board, String> {
-        let mut pieces = Vec::<Piece>::new();
+impl std::error::Error for FenError {}
+
+fn parse_piece_placement(placement: &str) -> Result<Mailbox, FenError> {
+    let mut mailbox = [None; 64];
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::BadPiecePlacement);
+    }
+    for (y, rank) in ranks.iter().enumerate() {
         let mut x = 0;
-        let mut y = 0;
-        let fen_vec: Vec<&str> = fen.split(" ").collect();
-        for v in fen_vec[0].chars() {
-            if v.is_digit(10) {
-                x += v.to_digit(10).unwrap();
+        for v in rank.chars() {
+            if let Some(n) = v.to_digit(10) {
+                if n == 0 || n > 8 {
+                    return Err(FenError::BadPiecePlacement);
+                }
+                x += n;
                 continue;
             }
-            if v == '/' {
-                y += 1;
-                x = 0;
-                continue;
+            if x >= 8 {
+                return Err(FenError::BadPiecePlacement);
             }
-            let piece = match char_to_piece_type(v.to_ascii_lowercase()) {
-                Err(_) => return Err(String::from("Invalid fen")),
-                Ok(p) =>
-                    Piece {
-                        x,
-                        y,
-                        ptype: p,
-                        color: if v.is_ascii_lowercase() { PieceColor::Black } else { PieceColor::White },
-                    }
-            };
-            pieces.push(piece);
+            let ptype = char_to_piece_type(v.to_ascii_lowercase()).map_err(|_| FenError::BadPiecePlacement)?;
+            let color = if v.is_ascii_lowercase() { PieceColor::Black } else { PieceColor::White };
+            mailbox[y * 8 + x as usize] = Some((ptype, color));
             x += 1;
         }
-        Ok(Board {
-            pieces,
+        if x != 8 {
+            return Err(FenError::BadPiecePlacement);
+        }
+    }
+    Ok(mailbox)
+}
+
+fn square_from_string(s: String) -> Result<Square, String> {
+    // Rank 8 is y = 0, matching the order pieces are listed in a FEN.
+    s.parse()
+}
+
+fn build_bitboards(mailbox: &Mailbox) -> ([Bitboard; 6], [Bitboard; 2]) {
+    let mut by_type = [Bitboard::EMPTY; 6];
+    let mut by_color = [Bitboard::EMPTY; 2];
+    for (index, piece) in mailbox.iter().enumerate() {
+        if let Some((ptype, color)) = piece {
+            by_type[*ptype as usize].set(Square::from_index(index as u8));
+            by_color[*color as usize].set(Square::from_index(index as u8));
+        }
+    }
+    (by_type, by_color)
+}
+
+// The first and eighth ranks, where pawns can't be.
+const BACK_RANKS: Bitboard = Bitboard(0xff00_0000_0000_00ff);
+
+impl Board {
+    // Makes a new board from a FEN notation.
+    pub fn new_from_fen(fen: String) -> Result<Board, FenError> {
+        let fen_vec: Vec<&str> = fen.split_whitespace().collect();
+        if fen_vec.len() < 6 {
+            return Err(FenError::MissingField);
+        }
+        if fen_vec.len() > 6 {
+            return Err(FenError::TooManyFields);
+        }
+        let mailbox = parse_piece_placement(fen_vec[0])?;
+        let (by_type, by_color) = build_bitboards(&mailbox);
+        let mut board = Board {
+            by_type,
+            by_color,
+            mailbox,
             current_move:
-            if fen_vec[1] == "w" { PieceColor::White } else if fen_vec[1] == "w" { PieceColor::Black } else { return Err(String::from("Invalid fen")); },
-            can_white_king_castle: fen_vec[2].contains("K"),
-            can_white_queen_castle: fen_vec[2].contains("Q"),
-            can_black_king_castle: fen_vec[2].contains("k"),
-            can_black_queen_castle: fen_vec[2].contains("q"),
+            if fen_vec[1] == "w" { PieceColor::White } else if fen_vec[1] == "b" { PieceColor::Black } else { return Err(FenError::BadSideToMove); },
+            castling_rights: match CastlingRights::from_fen(fen_vec[2]) {
+                Err(_) => return Err(FenError::BadCastlingField),
+                Ok(c) => c,
+            },
             en_passant_square: if fen_vec[3] == "-" { None } else {
                 match square_from_string(String::from(fen_vec[3])) {
-                    Err(_) => return Err(String::from("Invalid fen")),
+                    Err(_) => return Err(FenError::BadEnPassantSquare),
                     Ok(s) => Some(s),
                 }
             },
-            halfmove_clock: fen_vec[4].parse().unwrap(),
-            fullmove_num: fen_vec[5].parse().unwrap(),
+            halfmove_clock: fen_vec[4].parse().map_err(|_| FenError::BadHalfmoveClock)?,
+            fullmove_num: match fen_vec[5].parse() {
+                Ok(n) if n >= 1 => n,
+                _ => return Err(FenError::BadFullmoveNumber),
+            },
+            zobrist: 0,
+        };
+        board.zobrist = board.compute_zobrist();
+        board.validate()?;
+        Ok(board)
+    }
+
+    // Writes the board out in FEN notation. Parsing the result with
+    // new_from_fen gives back the same position.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for y in 0..8 {
+            let mut empty = 0;
+            for x in 0..8 {
+                match self.piece_at(Square::new(x, y)) {
+                    None => empty += 1,
+                    Some((ptype, color)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let c = piece_type_to_char(ptype);
+                        fen.push(if color == PieceColor::White { c.to_ascii_uppercase() } else { c });
+                    }
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y < 7 {
+                fen.push('/');
+            }
+        }
+        format!("{} {} {} {} {} {}",
+                fen,
+                if self.current_move == PieceColor::White { "w" } else { "b" },
+                self.castling_rights,
+                match self.en_passant_square {
+                    None => String::from("-"),
+                    Some(square) => square.to_string(),
+                },
+                self.halfmove_clock,
+                self.fullmove_num)
+    }
+
+    // Returns an id that is the same for any two boards with the same piece
+    // placement, side to move, castling rights and en passant square.
+    pub fn position_id(&self) -> PositionId {
+        let mut hasher = Fnv::new();
+        for s in self.mailbox.iter() {
+            hasher.write_u8(match s {
+                None => 0,
+                Some((ptype, color)) => {
+                    let kind = match ptype {
+                        PieceType::Pawn => 1,
+                        PieceType::Rook => 2,
+                        PieceType::Knight => 3,
+                        PieceType::Bishop => 4,
+                        PieceType::Queen => 5,
+                        PieceType::King => 6,
+                    };
+                    if *color == PieceColor::White { kind } else { kind + 6 }
+                }
+            });
+        }
+        hasher.write_u8(if self.current_move == PieceColor::White { 0 } else { 1 });
+        hasher.write_u8(self.castling_rights.bits());
+        match self.en_passant_square {
+            None => hasher.write_u8(0xff),
+            Some(square) => hasher.write_u8(square.index() as u8),
+        }
+        PositionId(hasher.finish())
+    }
+
+    // Like position_id, but only counts the en passant square when an en
+    // passant capture is actually legal. Two positions are the same for the
+    // repetition rules exactly when these ids match.
+    pub fn repetition_id(&self) -> PositionId {
+        let capturable = self.en_passant_square.is_some_and(|ep| {
+            self.legal_moves().iter().any(|mv| mv.to == ep && self.piece_at(mv.from).map(|p| p.0) == Some(PieceType::Pawn))
+        });
+        if capturable || self.en_passant_square.is_none() {
+            return self.position_id();
+        }
+        let mut board = self.clone();
+        board.en_passant_square = None;
+        board.position_id()
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling_rights
+    }
+
+    pub fn side_to_move(&self) -> PieceColor {
+        self.current_move
+    }
+
+    // Half moves since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u8 {
+        self.halfmove_clock
+    }
+
+    // Starts at 1 and goes up after each Black move.
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_num
+    }
+
+    // Board editing. These are meant for building positions by hand, e.g. in a
+    // GUI editor, and don't follow the rules of chess. Each one checks the
+    // resulting position is still sensible and leaves the board untouched if
+    // it isn't. Changing the pieces or the side to move clears the en passant
+    // square, since an edited position has no last move, and piece edits drop
+    // any castling rights whose king or rook has left its starting square.
+
+    pub fn set_side_to_move(&mut self, color: PieceColor) -> Result<(), String> {
+        self.edit(|board| {
+            board.current_move = color;
+            board.en_passant_square = None;
+        })
+    }
+
+    // A copy of the board with the given side to move, e.g. to see what the
+    // opponent would do if it were their turn. Fails if that side could then
+    // capture the other king.
+    pub fn with_side_to_move(&self, color: PieceColor) -> Result<Board, String> {
+        let mut board = self.clone();
+        if color != self.current_move {
+            board.set_side_to_move(color)?;
+        }
+        Ok(board)
+    }
+
+    pub fn set_castling(&mut self, rights: CastlingRights) -> Result<(), String> {
+        self.edit(|board| board.castling_rights = rights)
+    }
+
+    // Removes whatever is on a square and returns it.
+    pub fn clear_square(&mut self, square: Square) -> Result<Option<(PieceType, PieceColor)>, String> {
+        let removed = self.piece_at(square);
+        self.edit(|board| {
+            board.remove_piece_at(square);
+            board.after_piece_edit();
+        })?;
+        Ok(removed)
+    }
+
+    // Moves a piece to another square, replacing anything already there,
+    // without checking that the move is legal.
+    pub fn move_piece_unchecked(&mut self, from: Square, to: Square) -> Result<(), String> {
+        if self.piece_at(from).is_none() {
+            return Err(String::from("No piece on that square."));
+        }
+        self.edit(|board| {
+            board.move_pieces(Move::new(from, to));
+            board.after_piece_edit();
         })
     }
 
+    // Applies an edit and validates the result, restoring the old board if
+    // the edit made it invalid.
+    fn edit<F: FnOnce(&mut Board)>(&mut self, f: F) -> Result<(), String> {
+        let backup = self.clone();
+        self.zobrist ^= self.state_key();
+        f(self);
+        self.zobrist ^= self.state_key();
+        if let Err(e) = self.validate() {
+            *self = backup;
+            return Err(e.to_string());
+        }
+        Ok(())
+    }
+
+    fn after_piece_edit(&mut self) {
+        self.en_passant_square = None;
+        for color in [PieceColor::White, PieceColor::Black].iter() {
+            for side in [CastlingSide::King, CastlingSide::Queen].iter() {
+                if !self.castling_pieces_in_place(*color, *side) {
+                    self.castling_rights.remove(*color, *side);
+                }
+            }
+        }
+    }
+
+    // True if the king and rook for this castling move are on their
+    // starting squares.
+    fn castling_pieces_in_place(&self, color: PieceColor, side: CastlingSide) -> bool {
+        let y = if color == PieceColor::White { 7 } else { 0 };
+        let rook_x = if side == CastlingSide::King { 7 } else { 0 };
+        self.piece_at(Square::new(4, y)) == Some((PieceType::King, color))
+            && self.piece_at(Square::new(rook_x, y)) == Some((PieceType::Rook, color))
+    }
+
+    // Checks the things every position needs: one king each, no more pieces
+    // than a side can have, no pawns on the first or last rank, castling
+    // rights only where the king and rook haven't moved, an en passant
+    // square only right after a double pawn push, and the side that just
+    // moved not being left in check.
+    fn validate(&self) -> Result<(), FenError> {
+        for color in [PieceColor::White, PieceColor::Black].iter() {
+            let count = |ptype: PieceType| self.pieces(ptype, *color).count();
+            match count(PieceType::King) {
+                0 => return Err(FenError::MissingKing),
+                1 => {}
+                _ => return Err(FenError::TooManyKings),
+            }
+            if count(PieceType::Pawn) > 8 || self.color_pieces(*color).count() > 16 {
+                return Err(FenError::TooManyPieces);
+            }
+            for side in [CastlingSide::King, CastlingSide::Queen].iter() {
+                if self.castling_rights.has(*color, *side) && !self.castling_pieces_in_place(*color, *side) {
+                    return Err(FenError::BadCastlingField);
+                }
+            }
+        }
+        if !(self.by_type[PieceType::Pawn as usize] & BACK_RANKS).is_empty() {
+            return Err(FenError::PawnOnBackRank);
+        }
+        if let Some(square) = self.en_passant_square {
+            // The pawn that just moved two squares is one step past the en
+            // passant square, and the square it came from is now empty.
+            // The rank is checked first, since off the third or sixth rank
+            // those squares can be off the board.
+            let (target_y, dy) = if self.current_move == PieceColor::White { (2, 1) } else { (5, -1) };
+            if square.y() != target_y {
+                return Err(FenError::BadEnPassantSquare);
+            }
+            let pawn = Square::new(square.x(), (square.y() as i8 + dy) as u8);
+            let origin = Square::new(square.x(), (square.y() as i8 - dy) as u8);
+            if self.piece_at(square).is_some()
+                || self.piece_at(origin).is_some()
+                || self.piece_at(pawn) != Some((PieceType::Pawn, self.current_move.opposite())) {
+                return Err(FenError::BadEnPassantSquare);
+            }
+        }
+        if self.is_check(self.current_move.opposite()) {
+            return Err(FenError::OpponentInCheck);
+        }
+        Ok(())
+    }
+
+    // Removes and returns the piece on a square, keeping the bitboards and
+    // mailbox in sync.
+    fn remove_piece_at(&mut self, square: Square) -> Option<(PieceType, PieceColor)> {
+        let (ptype, color) = self.mailbox[square.index()].take()?;
+        self.zobrist ^= zobrist::piece_key(square, ptype, color);
+        self.by_type[ptype as usize].clear(square);
+        self.by_color[color as usize].clear(square);
+        Some((ptype, color))
+    }
+
+    // Puts a piece on an empty square.
+    fn put_piece(&mut self, square: Square, ptype: PieceType, color: PieceColor) {
+        debug_assert!(self.mailbox[square.index()].is_none());
+        self.mailbox[square.index()] = Some((ptype, color));
+        self.zobrist ^= zobrist::piece_key(square, ptype, color);
+        self.by_type[ptype as usize].set(square);
+        self.by_color[color as usize].set(square);
+    }
+
+    // Moves the piece on one square to another, empty, square.
+    fn relocate_piece(&mut self, from: Square, to: Square) {
+        if let Some((ptype, color)) = self.remove_piece_at(from) {
+            self.put_piece(to, ptype, color);
+        }
+    }
+
+    // Changes the type of the piece on a square, e.g. when a pawn promotes.
+    fn set_piece_type(&mut self, square: Square, ptype: PieceType) {
+        if let Some((_, color)) = self.remove_piece_at(square) {
+            self.put_piece(square, ptype, color);
+        }
+    }
+
+    // Returns the type and color of the piece on a square, if there is one.
+    pub fn piece_at(&self, square: Square) -> Option<(PieceType, PieceColor)> {
+        self.mailbox[square.index()]
+    }
+
+    // The squares holding pieces of this type and color.
+    pub fn pieces(&self, ptype: PieceType, color: PieceColor) -> Bitboard {
+        self.by_type[ptype as usize] & self.by_color[color as usize]
+    }
+
+    // The squares holding any of this side's pieces.
+    pub fn color_pieces(&self, color: PieceColor) -> Bitboard {
+        self.by_color[color as usize]
+    }
+
+    // The squares holding a piece of either color.
+    pub fn occupied(&self) -> Bitboard {
+        self.by_color[0] | self.by_color[1]
+    }
+
+    // How many of a side's bishops stand on squares of the given color.
+    pub fn bishops_on_color(&self, color: PieceColor, square_color: SquareColor) -> usize {
+        let squares = match square_color {
+            SquareColor::Light => Bitboard::LIGHT_SQUARES,
+            SquareColor::Dark => Bitboard::DARK_SQUARES,
+        };
+        (self.pieces(PieceType::Bishop, color) & squares).count() as usize
+    }
+
+    // True if the side has bishops on both colors of square.
+    pub fn bishop_pair(&self, color: PieceColor) -> bool {
+        self.bishops_on_color(color, SquareColor::Light) > 0 && self.bishops_on_color(color, SquareColor::Dark) > 0
+    }
+
     pub fn render(&self) {
         for y in 0..8 {
             for x in 0..8 {
-                let mut piece_on_square = false;
-                for v in &self.pieces {
-                    if v.x == x && v.y == y {
-                        let c = piece_type_to_char(v.ptype);
-                        print!("{} ", if v.color == PieceColor::White { c.to_uppercase().to_string() } else { c.to_string() });
-                        piece_on_square = true;
-                        break;
+                match self.piece_at(Square::new(x, y)) {
+                    None => print!("* "),
+                    Some((ptype, color)) => {
+                        let c = piece_type_to_char(ptype);
+                        print!("{} ", if color == PieceColor::White { c.to_uppercase().to_string() } else { c.to_string() });
                     }
                 }
-                if !piece_on_square {
-                    print!("* ");
-                }
             }
             println!();
         }
@@ -164,50 +564,195 @@ mod tests {
     #[test]
     fn test_new_from_fen() {
         let board = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).expect("");
+        let back_rank = [PieceType::Rook, PieceType::Knight, PieceType::Bishop, PieceType::Queen,
+                         PieceType::King, PieceType::Bishop, PieceType::Knight, PieceType::Rook];
+        let mut mailbox = [None; 64];
+        for x in 0..8 {
+            mailbox[x] = Some((back_rank[x], PieceColor::Black));
+            mailbox[8 + x] = Some((PieceType::Pawn, PieceColor::Black));
+            mailbox[48 + x] = Some((PieceType::Pawn, PieceColor::White));
+            mailbox[56 + x] = Some((back_rank[x], PieceColor::White));
+        }
+        let (by_type, by_color) = build_bitboards(&mailbox);
+        assert_eq!(board.pieces(PieceType::Pawn, PieceColor::White), Bitboard(0x00ff_0000_0000_0000));
+        assert_eq!(board.occupied().count(), 32);
         assert_eq!(Board {
-            pieces: vec![Piece { x: 0, y: 0, ptype: PieceType::Rook, color: PieceColor::Black },
-                         Piece { x: 1, y: 0, ptype: PieceType::Knight, color: PieceColor::Black },
-                         Piece { x: 2, y: 0, ptype: PieceType::Bishop, color: PieceColor::Black },
-                         Piece { x: 3, y: 0, ptype: PieceType::Queen, color: PieceColor::Black },
-                         Piece { x: 4, y: 0, ptype: PieceType::King, color: PieceColor::Black },
-                         Piece { x: 5, y: 0, ptype: PieceType::Bishop, color: PieceColor::Black },
-                         Piece { x: 6, y: 0, ptype: PieceType::Knight, color: PieceColor::Black },
-                         Piece { x: 7, y: 0, ptype: PieceType::Rook, color: PieceColor::Black },
-                         Piece { x: 0, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 1, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 2, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 3, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 4, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 5, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 6, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 7, y: 1, ptype: PieceType::Pawn, color: PieceColor::Black },
-                         Piece { x: 0, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 1, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 2, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 3, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 4, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 5, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 6, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 7, y: 6, ptype: PieceType::Pawn, color: PieceColor::White },
-                         Piece { x: 0, y: 7, ptype: PieceType::Rook, color: PieceColor::White },
-                         Piece { x: 1, y: 7, ptype: PieceType::Knight, color: PieceColor::White },
-                         Piece { x: 2, y: 7, ptype: PieceType::Bishop, color: PieceColor::White },
-                         Piece { x: 3, y: 7, ptype: PieceType::Queen, color: PieceColor::White },
-                         Piece { x: 4, y: 7, ptype: PieceType::King, color: PieceColor::White },
-                         Piece { x: 5, y: 7, ptype: PieceType::Bishop, color: PieceColor::White },
-                         Piece { x: 6, y: 7, ptype: PieceType::Knight, color: PieceColor::White },
-                         Piece { x: 7, y: 7, ptype: PieceType::Rook, color: PieceColor::White }],
+            by_type,
+            by_color,
+            mailbox,
             current_move: PieceColor::White,
-            can_white_king_castle: true,
-            can_white_queen_castle: true,
-            can_black_king_castle: true,
-            can_black_queen_castle: true,
+            castling_rights: CastlingRights::ALL,
             en_passant_square: None,
             halfmove_clock: 0,
             fullmove_num: 1,
+            zobrist: board.compute_zobrist(),
         }, board);
     }
 
+    #[test]
+    fn test_to_fen_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 12 40",
+            "8/8/8/8/8/8/8/k6K b - - 99 150",
+        ];
+        for fen in fens.iter() {
+            let board = Board::new_from_fen(String::from(*fen)).expect("");
+            assert_eq!(board.to_fen(), *fen);
+        }
+    }
+
+    #[test]
+    fn test_to_fen_after_moves() {
+        let mut board = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).expect("");
+        board.make_move(Move::new(Square::new(4, 6), Square::new(4, 4))).expect("");
+        board.make_move(Move::new(Square::new(2, 1), Square::new(2, 3))).expect("");
+        board.make_move(Move::new(Square::new(6, 7), Square::new(5, 5))).expect("");
+        assert_eq!(board.to_fen(), "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    }
+
+    #[test]
+    fn test_position_id() {
+        let start = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).expect("");
+        let later = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 3")).expect("");
+        let black = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1")).expect("");
+        let no_castle = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1")).expect("");
+        assert_eq!(start.position_id(), later.position_id());
+        assert_ne!(start.position_id(), black.position_id());
+        assert_ne!(start.position_id(), no_castle.position_id());
+        let code = start.position_id().to_short_code();
+        assert_eq!(PositionId::from_short_code(&code), Ok(start.position_id()));
+    }
+
+    #[test]
+    fn test_repetition_id() {
+        // No black pawn can take on e3, so the en passant square doesn't count.
+        let after_e4 = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")).expect("");
+        let no_ep = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")).expect("");
+        assert_ne!(after_e4.position_id(), no_ep.position_id());
+        assert_eq!(after_e4.repetition_id(), no_ep.repetition_id());
+
+        let capturable = Board::new_from_fen(String::from("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1")).expect("");
+        let not_capturable = Board::new_from_fen(String::from("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1")).expect("");
+        assert_ne!(capturable.repetition_id(), not_capturable.repetition_id());
+    }
+
+    #[test]
+    fn test_bishops() {
+        let board = Board::new_from_fen(String::from(STARTING_FEN)).expect("");
+        assert!(board.bishop_pair(PieceColor::White));
+        assert_eq!(board.bishops_on_color(PieceColor::Black, SquareColor::Light), 1);
+        assert_eq!(board.bishops_on_color(PieceColor::Black, SquareColor::Dark), 1);
+
+        // Two white bishops, both on dark squares (c1 and d2), and one black
+        // bishop on a light square (f5).
+        let board = Board::new_from_fen(String::from("4k3/8/8/5b2/8/8/3B4/2B1K3 w - - 0 1")).expect("");
+        assert!(!board.bishop_pair(PieceColor::White));
+        assert_eq!(board.bishops_on_color(PieceColor::White, SquareColor::Dark), 2);
+        assert_eq!(board.bishops_on_color(PieceColor::White, SquareColor::Light), 0);
+        assert_eq!(board.bishops_on_color(PieceColor::Black, SquareColor::Light), 1);
+        assert!(!board.bishop_pair(PieceColor::Black));
+    }
+
+    #[test]
+    fn test_piece_at() {
+        let board = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")).expect("");
+        assert_eq!(board.piece_at(Square::new(4, 4)), Some((PieceType::Pawn, PieceColor::White)));
+        assert_eq!(board.piece_at(Square::new(4, 6)), None);
+        assert_eq!(board.piece_at(Square::new(3, 0)), Some((PieceType::Queen, PieceColor::Black)));
+        assert_eq!(Board::new_from_fen(String::from("rnbqkbnrr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")), Err(FenError::BadPiecePlacement));
+    }
+
+    #[test]
+    fn test_editor_operations() {
+        let mut board = Board::new_from_fen(String::from("r3k2r/pppppppp/8/8/4P3/8/PPPP1PPP/R3K2R b KQkq e3 0 1")).expect("");
+        board.set_side_to_move(PieceColor::White).expect("");
+        assert_eq!(board.side_to_move(), PieceColor::White);
+        assert_eq!(board.en_passant_square, None);
+
+        // Taking the h1 rook away loses white's king side castling.
+        assert_eq!(board.clear_square(Square::new(7, 7)), Ok(Some((PieceType::Rook, PieceColor::White))));
+        assert_eq!(board.castling_rights().to_string(), "Qkq");
+        assert_eq!(board.piece_at(Square::new(7, 7)), None);
+
+        board.move_piece_unchecked(Square::new(4, 4), Square::new(4, 1)).expect("");
+        assert_eq!(board.piece_at(Square::new(4, 1)), Some((PieceType::Pawn, PieceColor::White)));
+        assert_eq!(board.piece_at(Square::new(4, 4)), None);
+        assert_eq!(board.occupied().count(), 20);
+    }
+
+    #[test]
+    fn test_with_side_to_move() {
+        let board = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")).expect("");
+        let white = board.with_side_to_move(PieceColor::White).expect("");
+        assert_eq!(white.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(board.with_side_to_move(PieceColor::Black), Ok(board.clone()));
+
+        // Black is in check, so white can't be the one to move.
+        let check = Board::new_from_fen(String::from("4k3/8/8/8/8/8/8/4RK2 b - - 0 1")).expect("");
+        assert!(check.with_side_to_move(PieceColor::White).is_err());
+    }
+
+    #[test]
+    fn test_editor_rejects_invalid_positions() {
+        let mut board = Board::new_from_fen(String::from("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1")).expect("");
+        let before = board.clone();
+        assert!(board.clear_square(Square::new(4, 7)).is_err());
+        assert!(board.move_piece_unchecked(Square::new(0, 6), Square::new(0, 0)).is_err());
+        assert!(board.move_piece_unchecked(Square::new(3, 3), Square::new(3, 4)).is_err());
+        assert_eq!(board, before);
+
+        board.set_castling(CastlingRights::from_fen("Kk").expect("")).expect("");
+        board.clear_square(Square::new(0, 7)).expect("");
+        let mut rights = board.castling_rights();
+        rights.grant(PieceColor::White, CastlingSide::Queen);
+        assert!(board.set_castling(rights).is_err());
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        let check = |fen: &str| Board::new_from_fen(String::from(fen)).err();
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), None);
+        assert_eq!(check(""), Some(FenError::MissingField));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"), Some(FenError::MissingField));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 x"), Some(FenError::TooManyFields));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1"), Some(FenError::BadPiecePlacement));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/7/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::BadPiecePlacement));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/9/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::BadPiecePlacement));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1"), Some(FenError::BadPiecePlacement));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1"), Some(FenError::BadSideToMove));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1"), Some(FenError::BadCastlingField));
+        assert_eq!(check("rnbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::BadCastlingField));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1"), Some(FenError::BadEnPassantSquare));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1"), Some(FenError::BadEnPassantSquare));
+        assert_eq!(check("4k3/8/8/8/8/8/8/K7 b - h1 0 1"), Some(FenError::BadEnPassantSquare));
+        assert_eq!(check("4k3/8/8/8/8/8/8/K7 w - h8 0 1"), Some(FenError::BadEnPassantSquare));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1"), Some(FenError::BadHalfmoveClock));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0"), Some(FenError::BadFullmoveNumber));
+        assert_eq!(check("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1"), Some(FenError::MissingKing));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1"), Some(FenError::TooManyKings));
+        assert_eq!(check("rnbqkbnr/pppppppp/8/8/8/P7/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Some(FenError::TooManyPieces));
+        assert_eq!(check("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"), Some(FenError::PawnOnBackRank));
+        assert_eq!(check("4k2R/8/8/8/8/8/8/4K3 w - - 0 1"), Some(FenError::OpponentInCheck));
+    }
+
+    #[test]
+    fn test_castling_rights_from_fen() {
+        let board = Board::new_from_fen(String::from("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1")).expect("");
+        assert_eq!(board.castling_rights().to_string(), "Kq");
+        assert!(Board::new_from_fen(String::from("r3k2r/8/8/8/8/8/8/R3K2R w KX - 0 1")).is_err());
+    }
+
+    #[test]
+    fn test_square_from_string() {
+        assert_eq!(square_from_string(String::from("e3")), Ok(Square::new(4, 5)));
+        assert_eq!(square_from_string(String::from("a8")), Ok(Square::new(0, 0)));
+        assert!(square_from_string(String::from("i3")).is_err());
+        assert!(square_from_string(String::from("e9")).is_err());
+        assert!(square_from_string(String::from("e")).is_err());
+    }
+
     #[test]
     fn test_char_to_piece_type() {
         assert_eq!(char_to_piece_type('p'), Ok(PieceType::Pawn));
@@ -0,0 +1,190 @@
+use crate::bitboard::{self, Bitboard};
+use crate::square::Square;
+
+use super::{Board, PieceColor, PieceType};
+
+impl Board {
+    // The pieces of the given color that attack a square. Looks outwards
+    // from the square: a knight on the square would attack any enemy
+    // knights that attack it, and likewise for the other pieces.
+    fn attackers(&self, square: Square, by_color: PieceColor) -> Bitboard {
+        let occupied = self.occupied();
+        let queens = self.pieces(PieceType::Queen, by_color);
+        (bitboard::pawn_attacks(square, by_color.opposite()) & self.pieces(PieceType::Pawn, by_color))
+            | (bitboard::knight_attacks(square) & self.pieces(PieceType::Knight, by_color))
+            | (bitboard::king_attacks(square) & self.pieces(PieceType::King, by_color))
+            | (bitboard::bishop_attacks(square, occupied) & (self.pieces(PieceType::Bishop, by_color) | queens))
+            | (bitboard::rook_attacks(square, occupied) & (self.pieces(PieceType::Rook, by_color) | queens))
+    }
+
+    // Returns the squares of all pieces of the given color that attack a
+    // square.
+    pub fn attackers_of(&self, square: Square, by_color: PieceColor) -> Vec<Square> {
+        self.attackers(square, by_color).iter().collect()
+    }
+
+    // True if any piece of the given color attacks the square. The square
+    // itself may be empty or hold a piece of either color.
+    pub fn is_square_attacked(&self, square: Square, by_color: PieceColor) -> bool {
+        !self.attackers(square, by_color).is_empty()
+    }
+
+    // True if the king of the given color is attacked.
+    pub fn is_check(&self, color: PieceColor) -> bool {
+        match self.king_square(color) {
+            None => false,
+            Some(king) => self.is_square_attacked(king, color.opposite()),
+        }
+    }
+
+    // True if the side to move is in check and has no legal moves.
+    pub fn is_checkmate(&self) -> bool {
+        self.is_check(self.current_move) && self.legal_moves().is_empty()
+    }
+
+    // True if the side to move is not in check but has no legal moves.
+    pub fn is_stalemate(&self) -> bool {
+        !self.is_check(self.current_move) && self.legal_moves().is_empty()
+    }
+
+    // Fifty moves by each side without a capture or pawn move, so either
+    // player may claim a draw. A mate on the fiftieth move still wins.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100 && !self.is_checkmate()
+    }
+
+    // Seventy-five moves by each side without a capture or pawn move. Under
+    // FIDE rules the game is drawn at once, with no claim needed.
+    pub fn is_seventy_five_move_draw(&self) -> bool {
+        self.halfmove_clock >= 150 && !self.is_checkmate()
+    }
+
+    // Neither side has enough pieces left to ever mate: bare kings, a single
+    // minor piece, or only bishops that all stand on the same color. Other
+    // positions, like two knights against a king, can still end in mate if
+    // the defender helps, so they aren't counted.
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy = self.by_type[PieceType::Pawn as usize] | self.by_type[PieceType::Rook as usize]
+            | self.by_type[PieceType::Queen as usize];
+        if !heavy.is_empty() {
+            return false;
+        }
+        let bishops = self.by_type[PieceType::Bishop as usize];
+        let minors = (self.by_type[PieceType::Knight as usize] | bishops).count();
+        let one_color = (bishops & Bitboard::LIGHT_SQUARES).is_empty() || (bishops & Bitboard::DARK_SQUARES).is_empty();
+        minors <= 1 || (bishops.count() == minors && one_color)
+    }
+
+    pub(crate) fn king_square(&self, color: PieceColor) -> Option<Square> {
+        self.pieces(PieceType::King, color).lsb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    #[test]
+    fn test_is_check() {
+        let b = board("4k3/8/8/8/1b6/8/8/4K3 w - - 0 1");
+        assert!(b.is_check(PieceColor::White));
+        assert!(!b.is_check(PieceColor::Black));
+        assert!(!board("4k3/8/8/8/1b6/8/3P4/4K3 w - - 0 1").is_check(PieceColor::White));
+        assert!(board("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1").is_check(PieceColor::White));
+        assert!(board("4k3/8/8/8/8/5n2/8/4K3 w - - 0 1").is_check(PieceColor::White));
+    }
+
+    #[test]
+    fn test_checkmate_and_stalemate() {
+        // Fool's mate.
+        let mate = board("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert!(mate.is_checkmate());
+        assert!(!mate.is_stalemate());
+
+        let stalemate = board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert!(stalemate.is_stalemate());
+        assert!(!stalemate.is_checkmate());
+
+        let start = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert!(!start.is_checkmate());
+        assert!(!start.is_stalemate());
+    }
+
+    #[test]
+    fn test_move_rule_draws() {
+        let fen = |clock: u32| format!("4k3/8/8/8/8/8/4P3/R3K3 w - - {} 80", clock);
+        assert!(!board(&fen(99)).is_fifty_move_draw());
+        assert!(board(&fen(100)).is_fifty_move_draw());
+        assert!(!board(&fen(100)).is_seventy_five_move_draw());
+        assert!(board(&fen(150)).is_seventy_five_move_draw());
+
+        // Mate on the last move still counts.
+        let mate = board("R3k3/8/4K3/8/8/8/8/8 b - - 150 120");
+        assert!(mate.is_checkmate());
+        assert!(!mate.is_fifty_move_draw());
+        assert!(!mate.is_seventy_five_move_draw());
+
+        // A pawn move starts the count again.
+        let mut b = board(&fen(99));
+        b.make_move(Move::new(Square::new(4, 6), Square::new(4, 4))).expect("");
+        assert_eq!(b.halfmove_clock, 0);
+        let mut b = board(&fen(99));
+        b.make_move(Move::new(Square::new(0, 7), Square::new(0, 6))).expect("");
+        assert!(b.is_fifty_move_draw());
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        let dead = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",
+            // Bishops on c1, e3 and black's on b8, all dark squares.
+            "1b2k3/8/8/8/8/4B3/8/2B1K3 w - - 0 1",
+        ];
+        for fen in dead.iter() {
+            assert!(board(fen).is_insufficient_material(), "{}", fen);
+        }
+        let alive = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K1n1 w - - 0 1",
+            // Opposite colored bishops can still mate with help.
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        ];
+        for fen in alive.iter() {
+            assert!(!board(fen).is_insufficient_material(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_is_square_attacked() {
+        let b = board("4k3/8/8/3r4/8/2N2B2/8/3RK3 w - - 0 1");
+        // The rook on d5 sees down to d1 and along the fifth rank.
+        assert!(b.is_square_attacked(Square::new(3, 6), PieceColor::Black));
+        assert!(b.is_square_attacked(Square::new(0, 3), PieceColor::Black));
+        assert!(!b.is_square_attacked(Square::new(4, 4), PieceColor::Black));
+        // Pawns only attack diagonally forwards.
+        let b = board("4k3/8/8/8/4P3/8/8/4K3 w - - 0 1");
+        assert!(b.is_square_attacked(Square::new(3, 3), PieceColor::White));
+        assert!(!b.is_square_attacked(Square::new(4, 3), PieceColor::White));
+        assert!(!b.is_square_attacked(Square::new(3, 5), PieceColor::White));
+    }
+
+    #[test]
+    fn test_attackers_of() {
+        let b = board("4k3/8/8/3r4/8/2N2B2/8/3RK3 w - - 0 1");
+        let mut attackers = b.attackers_of(Square::new(3, 3), PieceColor::White);
+        attackers.sort();
+        assert_eq!(attackers, vec![Square::new(2, 5), Square::new(5, 5), Square::new(3, 7)]);
+        assert!(b.attackers_of(Square::new(3, 3), PieceColor::Black).is_empty());
+    }
+}
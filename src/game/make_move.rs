@@ -0,0 +1,282 @@
+use crate::castling::{CastlingRights, CastlingSide};
+use crate::moves::{Move, MoveError};
+use crate::square::Square;
+
+use super::{Board, PieceColor, PieceType};
+
+// What make_move returns so unmake_move can put the board back exactly as it
+// was: anything the move captured and the state that can't be worked out
+// from the move itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UndoInfo {
+    // The captured piece and the square it was taken on, which for en
+    // passant isn't the square the capturing pawn moved to.
+    captured: Option<(Square, PieceType, PieceColor)>,
+    castling_rights: CastlingRights,
+    en_passant_square: Option<Square>,
+    halfmove_clock: u8,
+    fullmove_num: u32,
+}
+
+impl UndoInfo {
+    // The type and color of the piece the move captured, if any.
+    pub fn captured(&self) -> Option<(PieceType, PieceColor)> {
+        self.captured.map(|(_, ptype, color)| (ptype, color))
+    }
+}
+
+impl Board {
+    // Plays a move, updating everything about the position: the pieces, side
+    // to move, castling rights, en passant square and both clocks.
+    // The board is unchanged if the move isn't legal.
+    pub fn make_move(&mut self, mv: Move) -> Result<UndoInfo, MoveError> {
+        let (_, color) = match self.piece_at(mv.from) {
+            None => return Err(MoveError::NoPiece),
+            Some(p) => p,
+        };
+        if color != self.current_move {
+            return Err(MoveError::WrongColor);
+        }
+        if !self.legal_moves().contains(&mv) {
+            return Err(MoveError::Illegal);
+        }
+        Ok(self.make_move_unchecked(mv))
+    }
+
+    // Takes back a move made with make_move. It must be the last move made on
+    // this board, with the UndoInfo that make_move returned for it.
+    pub fn unmake_move(&mut self, mv: Move, undo: UndoInfo) {
+        if let Some((rook_from, rook_to)) = self.castling_rook_move(mv.to, mv) {
+            self.relocate_piece(rook_to, rook_from);
+        }
+        if mv.promotion.is_some() {
+            self.set_piece_type(mv.to, PieceType::Pawn);
+        }
+        self.relocate_piece(mv.to, mv.from);
+        if let Some((square, ptype, color)) = undo.captured {
+            self.put_piece(square, ptype, color);
+        }
+        self.zobrist ^= self.state_key();
+        self.castling_rights = undo.castling_rights;
+        self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_num = undo.fullmove_num;
+        self.current_move = self.current_move.opposite();
+        self.zobrist ^= self.state_key();
+    }
+
+    // make_move without the legality check, for callers that already took
+    // the move from legal_moves.
+    pub(crate) fn make_move_unchecked(&mut self, mv: Move) -> UndoInfo {
+        let (ptype, color) = self.piece_at(mv.from).expect("no piece to move");
+        let mut undo = UndoInfo {
+            captured: None,
+            castling_rights: self.castling_rights,
+            en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_num: self.fullmove_num,
+        };
+        // The side to move, castling rights and en passant square all change
+        // below, so their keys come out of the hash now and go back in at the
+        // end.
+        self.zobrist ^= self.state_key();
+
+        if let Some((rook_from, rook_to)) = self.castling_rook_move(mv.from, mv) {
+            self.relocate_piece(rook_from, rook_to);
+        }
+        undo.captured = self.move_pieces(mv).map(|(ptype, color)| (mv.to, ptype, color));
+        // An en passant capture lands behind the pawn it takes.
+        if ptype == PieceType::Pawn && Some(mv.to) == self.en_passant_square && mv.from.x() != mv.to.x() {
+            let square = Square::new(mv.to.x(), mv.from.y());
+            undo.captured = self.remove_piece_at(square).map(|(ptype, color)| (square, ptype, color));
+        }
+        let is_capture = undo.captured.is_some();
+        if let Some(promotion) = mv.promotion {
+            self.set_piece_type(mv.to, promotion);
+        }
+
+        // Moving the king or a rook off its starting square, or capturing a
+        // rook on its starting square, loses the matching castling rights.
+        if ptype == PieceType::King {
+            self.castling_rights.remove_color(color);
+        }
+        for square in [mv.from, mv.to].iter() {
+            if let Some((rook_color, side)) = castling_rook_home(*square) {
+                self.castling_rights.remove(rook_color, side);
+            }
+        }
+
+        self.en_passant_square = None;
+        if ptype == PieceType::Pawn && (mv.from.y() as i8 - mv.to.y() as i8).abs() == 2 {
+            self.en_passant_square = Some(Square::new(mv.from.x(), (mv.from.y() + mv.to.y()) / 2));
+        }
+
+        if ptype == PieceType::Pawn || is_capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        if color == PieceColor::Black {
+            self.fullmove_num = self.fullmove_num.saturating_add(1);
+        }
+        self.current_move = color.opposite();
+        self.zobrist ^= self.state_key();
+        undo
+    }
+
+    // If the king standing on `king_square` is castling with this move,
+    // returns where its rook moves from and to. Castling is written as the
+    // king moving two squares towards the rook.
+    fn castling_rook_move(&self, king_square: Square, mv: Move) -> Option<(Square, Square)> {
+        match self.piece_at(king_square) {
+            Some((PieceType::King, _)) => {}
+            _ => return None,
+        }
+        let y = mv.from.y();
+        match mv.to.x() as i8 - mv.from.x() as i8 {
+            2 => Some((Square::new(7, y), Square::new(5, y))),
+            -2 => Some((Square::new(0, y), Square::new(3, y))),
+            _ => None,
+        }
+    }
+}
+
+// If a rook starts on this square, returns whose rook and which side it castles on.
+fn castling_rook_home(square: Square) -> Option<(PieceColor, CastlingSide)> {
+    match (square.x(), square.y()) {
+        (0, 7) => Some((PieceColor::White, CastlingSide::Queen)),
+        (7, 7) => Some((PieceColor::White, CastlingSide::King)),
+        (0, 0) => Some((PieceColor::Black, CastlingSide::Queen)),
+        (7, 0) => Some((PieceColor::Black, CastlingSide::King)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    fn mv(from: &str, to: &str) -> Move {
+        let parse = |s: &str| Square::new(s.as_bytes()[0] - b'a', b'8' - s.as_bytes()[1]);
+        Move::new(parse(from), parse(to))
+    }
+
+    #[test]
+    fn test_make_move_updates_state() {
+        let mut b = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        b.make_move(mv("e2", "e4")).expect("");
+        assert_eq!(b.position_id(), board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").position_id());
+        assert_eq!((b.halfmove_clock, b.fullmove_num), (0, 1));
+        b.make_move(mv("g8", "f6")).expect("");
+        assert_eq!(b.position_id(), board("rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2").position_id());
+        assert_eq!((b.halfmove_clock, b.fullmove_num), (1, 2));
+        b.make_move(mv("e1", "e2")).expect("");
+        assert_eq!(b.castling_rights().to_string(), "kq");
+        assert_eq!(b.side_to_move(), PieceColor::Black);
+        assert_eq!(b.halfmove_clock, 2);
+        b.make_move(mv("f6", "e4")).expect("");
+        assert_eq!(b.halfmove_clock, 0);
+        assert_eq!(b.fullmove_num, 3);
+        assert_eq!(b.piece_at(Square::new(4, 4)), Some((PieceType::Knight, PieceColor::Black)));
+
+        // The move number stops at its largest value instead of overflowing.
+        let mut b = board("4k3/8/8/8/8/8/8/4K3 b - - 0 4294967295");
+        b.make_move(mv("e8", "d8")).expect("");
+        assert_eq!(b.fullmove_num, u32::MAX);
+    }
+
+    #[test]
+    fn test_rook_moves_and_captures_remove_castling() {
+        let mut b = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        b.make_move(mv("a1", "a8")).expect("");
+        assert_eq!(b.castling_rights().to_string(), "Kk");
+
+        let mut b = board("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        b.make_move(mv("h8", "h1")).expect("");
+        assert_eq!(b.castling_rights().to_string(), "Qq");
+    }
+
+    #[test]
+    fn test_unmake_restores_board() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 7 20",
+        ];
+        for fen in fens.iter() {
+            let start = board(fen);
+            for m in start.legal_moves() {
+                let mut b = start.clone();
+                let undo = b.make_move(m).expect("");
+                b.unmake_move(m, undo);
+                assert_eq!(b, start);
+            }
+        }
+    }
+
+    #[test]
+    fn test_undo_info_captured() {
+        let mut b = board("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        let undo = b.make_move(mv("e4", "d5")).expect("");
+        assert_eq!(undo.captured(), Some((PieceType::Pawn, PieceColor::Black)));
+        let undo = b.make_move(mv("e8", "e7")).expect("");
+        assert_eq!(undo.captured(), None);
+    }
+
+    #[test]
+    fn test_castling_moves_the_rook() {
+        let mut b = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 3 10");
+        let start = b.clone();
+        let undo = b.make_move(mv("e1", "g1")).expect("");
+        assert_eq!(b.position_id(), board("r3k2r/8/8/8/8/8/8/R4RK1 b kq - 4 10").position_id());
+        b.unmake_move(mv("e1", "g1"), undo);
+        assert_eq!(b, start);
+
+        b.make_move(mv("e1", "c1")).expect("");
+        // The rook now on d1 covers d8, so black can only castle king side.
+        assert_eq!(b.make_move(mv("e8", "c8")), Err(MoveError::Illegal));
+        b.make_move(mv("e8", "g8")).expect("");
+        assert_eq!(b.position_id(), board("r4rk1/8/8/8/8/8/8/2KR3R w - - 5 11").position_id());
+    }
+
+    #[test]
+    fn test_en_passant_removes_the_pawn() {
+        let mut b = board("rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        b.make_move(mv("e2", "e4")).expect("");
+        let before = b.clone();
+        let undo = b.make_move(mv("d4", "e3")).expect("");
+        assert_eq!(undo.captured(), Some((PieceType::Pawn, PieceColor::White)));
+        assert_eq!(b.piece_at(Square::new(4, 4)), None);
+        assert_eq!(b.piece_at(Square::new(4, 5)), Some((PieceType::Pawn, PieceColor::Black)));
+        assert_eq!(b.occupied().count(), 31);
+        b.unmake_move(mv("d4", "e3"), undo);
+        assert_eq!(b, before);
+    }
+
+    #[test]
+    fn test_promotion() {
+        let mut b = board("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        let before = b.clone();
+        assert_eq!(b.make_move(mv("a7", "a8")), Err(MoveError::Illegal));
+        let promotion = Move::new_promotion(Square::new(0, 1), Square::new(1, 0), PieceType::Knight);
+        let undo = b.make_move(promotion).expect("");
+        assert_eq!(b.piece_at(Square::new(1, 0)), Some((PieceType::Knight, PieceColor::White)));
+        assert_eq!(undo.captured(), Some((PieceType::Knight, PieceColor::Black)));
+        b.unmake_move(promotion, undo);
+        assert_eq!(b, before);
+    }
+
+    #[test]
+    fn test_make_move_errors() {
+        let mut b = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let before = b.clone();
+        assert_eq!(b.make_move(mv("e4", "e5")), Err(MoveError::NoPiece));
+        assert_eq!(b.make_move(mv("e7", "e5")), Err(MoveError::WrongColor));
+        assert_eq!(b.make_move(mv("e2", "e5")), Err(MoveError::Illegal));
+        assert_eq!(b, before);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::bitboard::{self, Bitboard};
+use crate::castling::CastlingSide;
+use crate::moves::Move;
+use crate::square::{Direction, Square};
+
+use super::{Board, PieceColor, PieceType};
+
+fn push_moves(from: Square, targets: Bitboard, moves: &mut Vec<Move>) {
+    moves.extend(targets.iter().map(|to| Move::new(from, to)));
+}
+
+impl Board {
+    // Generates every move for the side to move that follows the way the
+    // pieces move, without checking whether it leaves the king in check.
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let color = self.current_move;
+        let targets = !self.color_pieces(color);
+        let occupied = self.occupied();
+        for from in self.pieces(PieceType::Pawn, color) {
+            self.pawn_moves(from, &mut moves);
+        }
+        for from in self.pieces(PieceType::Knight, color) {
+            push_moves(from, bitboard::knight_attacks(from) & targets, &mut moves);
+        }
+        for from in self.pieces(PieceType::Bishop, color) {
+            push_moves(from, bitboard::bishop_attacks(from, occupied) & targets, &mut moves);
+        }
+        for from in self.pieces(PieceType::Rook, color) {
+            push_moves(from, bitboard::rook_attacks(from, occupied) & targets, &mut moves);
+        }
+        for from in self.pieces(PieceType::Queen, color) {
+            push_moves(from, bitboard::queen_attacks(from, occupied) & targets, &mut moves);
+        }
+        for from in self.pieces(PieceType::King, color) {
+            push_moves(from, bitboard::king_attacks(from) & targets, &mut moves);
+            self.castling_moves(from, &mut moves);
+        }
+        moves
+    }
+
+    // Generates every legal move for the side to move: the pseudo-legal
+    // moves minus any that would leave their own king attacked. This covers
+    // pinned pieces, moving the king onto an attacked square and moves that
+    // fail to get out of check.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.pseudo_legal_moves().into_iter().filter(|m| self.is_legal_after(*m)).collect()
+    }
+
+    // Plays a pseudo-legal move on a copy of the board and checks the mover's
+    // king isn't attacked afterwards.
+    fn is_legal_after(&self, mv: Move) -> bool {
+        let mut board = self.clone();
+        board.make_move_unchecked(mv);
+        match board.king_square(self.current_move) {
+            None => true,
+            Some(king) => !board.is_square_attacked(king, self.current_move.opposite()),
+        }
+    }
+
+    // Moves the piece on mv.from to mv.to, capturing anything there, and
+    // returns the captured piece. Only the pieces change; side to move,
+    // castling and the clocks are left alone.
+    pub(super) fn move_pieces(&mut self, mv: Move) -> Option<(PieceType, PieceColor)> {
+        let captured = self.remove_piece_at(mv.to);
+        self.relocate_piece(mv.from, mv.to);
+        captured
+    }
+
+    // Castling needs the right to still be there, the squares between king
+    // and rook to be empty, and the king not to be in check or pass over an
+    // attacked square. Whether the square it lands on is attacked is left to
+    // the usual legal move check.
+    fn castling_moves(&self, from: Square, moves: &mut Vec<Move>) {
+        let color = self.current_move;
+        let y = if color == PieceColor::White { 7 } else { 0 };
+        if from != Square::new(4, y) || self.is_square_attacked(from, color.opposite()) {
+            return;
+        }
+        let sides = [(CastlingSide::King, 5..7, 5, 6), (CastlingSide::Queen, 1..4, 3, 2)];
+        for (side, empty, passed, to) in sides.iter() {
+            if !self.castling_rights.has(color, *side) {
+                continue;
+            }
+            if empty.clone().any(|x| self.piece_at(Square::new(x, y)).is_some()) {
+                continue;
+            }
+            if self.is_square_attacked(Square::new(*passed, y), color.opposite()) {
+                continue;
+            }
+            moves.push(Move::new(from, Square::new(*to, y)));
+        }
+    }
+
+    // Adds a pawn move, or all four promotions if it reaches the last rank.
+    fn push_pawn_move(from: Square, to: Square, moves: &mut Vec<Move>) {
+        if to.y() == 0 || to.y() == 7 {
+            for ptype in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight].iter() {
+                moves.push(Move::new_promotion(from, to, *ptype));
+            }
+        } else {
+            moves.push(Move::new(from, to));
+        }
+    }
+
+    fn pawn_moves(&self, from: Square, moves: &mut Vec<Move>) {
+        let color = self.current_move;
+        let (forward, start_y) = if color == PieceColor::White { (Direction::North, 6) } else { (Direction::South, 1) };
+        if let Some(to) = from.offset(forward) {
+            if self.piece_at(to).is_none() {
+                Board::push_pawn_move(from, to, moves);
+                if from.y() == start_y {
+                    let two = to.offset(forward).unwrap();
+                    if self.piece_at(two).is_none() {
+                        moves.push(Move::new(from, two));
+                    }
+                }
+            }
+        }
+        let mut targets = self.color_pieces(color.opposite());
+        if let Some(square) = self.en_passant_square {
+            targets.set(square);
+        }
+        for to in bitboard::pawn_attacks(from, color) & targets {
+            Board::push_pawn_move(from, to, moves);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    fn sq(name: &str) -> Square {
+        let bytes = name.as_bytes();
+        Square::new(bytes[0] - b'a', b'8' - bytes[1])
+    }
+
+    #[test]
+    fn test_start_position_moves() {
+        let moves = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").pseudo_legal_moves();
+        assert_eq!(moves.len(), 20);
+        assert!(moves.contains(&Move::new(sq("e2"), sq("e4"))));
+        assert!(moves.contains(&Move::new(sq("g1"), sq("f3"))));
+        assert!(!moves.contains(&Move::new(sq("f1"), sq("c4"))));
+
+        let black = board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").pseudo_legal_moves();
+        assert_eq!(black.len(), 20);
+        assert!(black.contains(&Move::new(sq("d7"), sq("d5"))));
+    }
+
+    #[test]
+    fn test_captures_and_blocking() {
+        let moves = board("4k3/8/8/3p4/2P1r3/8/8/R3K3 w - - 0 1").pseudo_legal_moves();
+        // Pawn takes on d5 or pushes to c5.
+        assert!(moves.contains(&Move::new(sq("c4"), sq("d5"))));
+        assert!(moves.contains(&Move::new(sq("c4"), sq("c5"))));
+        // The rook slides up the a file and along the first rank up to the king.
+        assert!(moves.contains(&Move::new(sq("a1"), sq("a8"))));
+        assert!(moves.contains(&Move::new(sq("a1"), sq("d1"))));
+        assert!(!moves.contains(&Move::new(sq("a1"), sq("e1"))));
+        // Pseudo-legal moves don't care that e2 is attacked by the rook.
+        assert!(moves.contains(&Move::new(sq("e1"), sq("e2"))));
+        assert_eq!(moves.len(), 2 + 10 + 5);
+    }
+
+    #[test]
+    fn test_legal_moves_pins_and_king_safety() {
+        // The e2 knight is pinned by the e8 rook, and the king can't step onto
+        // the d file, which the d8 rook covers.
+        let moves = board("3rr1k1/8/8/8/8/8/4N3/4K3 w - - 0 1").legal_moves();
+        assert!(!moves.iter().any(|m| m.from == sq("e2")));
+        assert!(!moves.contains(&Move::new(sq("e1"), sq("d1"))));
+        assert!(!moves.contains(&Move::new(sq("e1"), sq("d2"))));
+        assert!(moves.contains(&Move::new(sq("e1"), sq("f1"))));
+        assert_eq!(moves.len(), 2);
+    }
+
+    #[test]
+    fn test_legal_moves_in_check() {
+        // Black's bishop checks from b4; white can block with the pawn or move away.
+        let moves = board("4k3/8/8/8/1b6/8/2P5/R3K3 w - - 0 1").legal_moves();
+        let mut found: Vec<String> = moves.iter().map(|m| format!("{}{}", m.from, m.to)).collect();
+        found.sort();
+        assert_eq!(found, vec!["c2c3", "e1d1", "e1e2", "e1f1", "e1f2"]);
+
+        // A king can't capture a protected piece.
+        let moves = board("4k3/8/8/8/8/8/3q4/3rK3 w - - 0 1").legal_moves();
+        assert!(moves.is_empty());
+    }
+
+    #[test]
+    fn test_castling_generation() {
+        let castles = |fen: &str| -> Vec<String> {
+            let mut found: Vec<String> = board(fen).legal_moves().iter()
+                .filter(|m| m.from == sq("e1") && (m.to == sq("g1") || m.to == sq("c1")))
+                .map(|m| m.to.to_string())
+                .collect();
+            found.sort();
+            found
+        };
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"), vec!["c1", "g1"]);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1"), vec!["g1"]);
+        // Blocked by a piece between king and rook, including on b1.
+        assert_eq!(castles("r3k2r/8/8/8/8/8/8/RN2K1NR w KQkq - 0 1"), Vec::<String>::new());
+        // Out of check, through check, and into check.
+        assert_eq!(castles("r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1"), Vec::<String>::new());
+        assert_eq!(castles("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1"), vec!["c1"]);
+        assert_eq!(castles("r3k2r/8/8/8/8/8/6r1/R3K2R w KQkq - 0 1"), vec!["c1"]);
+        // b1 being attacked doesn't stop queen side castling.
+        assert_eq!(castles("r3k2r/8/8/8/8/8/1r6/R3K2R w KQkq - 0 1"), vec!["c1", "g1"]);
+
+        let black = board("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").legal_moves();
+        assert!(black.contains(&Move::new(sq("e8"), sq("g8"))));
+        assert!(black.contains(&Move::new(sq("e8"), sq("c8"))));
+    }
+
+    #[test]
+    fn test_en_passant_generation() {
+        let moves = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").legal_moves();
+        assert!(moves.contains(&Move::new(sq("e5"), sq("d6"))));
+        // Without the en passant square the capture isn't there.
+        let moves = board("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").legal_moves();
+        assert!(!moves.contains(&Move::new(sq("e5"), sq("d6"))));
+        // Taking would leave both pawns off the fifth rank and the king in
+        // check from the rook.
+        let moves = board("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1").legal_moves();
+        assert!(!moves.contains(&Move::new(sq("b5"), sq("c6"))));
+        assert!(moves.contains(&Move::new(sq("b5"), sq("b6"))));
+    }
+
+    #[test]
+    fn test_promotion_generation() {
+        let moves = board("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").legal_moves();
+        let promotions: Vec<&Move> = moves.iter().filter(|m| m.from == sq("a7")).collect();
+        assert_eq!(promotions.len(), 8);
+        assert!(promotions.iter().all(|m| m.promotion.is_some()));
+        assert!(moves.contains(&Move::new_promotion(sq("a7"), sq("b8"), PieceType::Rook)));
+        assert!(moves.contains(&Move::new_promotion(sq("a7"), sq("a8"), PieceType::Knight)));
+
+        let black = board("4k3/8/8/8/8/8/6p1/4K2R b - - 0 1").legal_moves();
+        assert!(black.contains(&Move::new_promotion(sq("g2"), sq("h1"), PieceType::Queen)));
+        assert!(black.contains(&Move::new_promotion(sq("g2"), sq("g1"), PieceType::Bishop)));
+    }
+
+    #[test]
+    fn test_blocked_pawn() {
+        let moves = board("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1").pseudo_legal_moves();
+        assert!(!moves.iter().any(|m| m.from == sq("e2")));
+        let moves = board("4k3/8/8/8/4n3/8/4P3/4K3 w - - 0 1").pseudo_legal_moves();
+        assert_eq!(moves.iter().filter(|m| m.from == sq("e2")).count(), 1);
+    }
+}
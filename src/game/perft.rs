@@ -0,0 +1,107 @@
+use crate::moves::Move;
+
+use super::Board;
+
+impl Board {
+    // Counts the leaf nodes of the legal move tree to the given depth. The
+    // counts for well known positions are published, so this is the standard
+    // way to check the move generator.
+    pub fn perft(&self, depth: u32) -> u64 {
+        let mut board = self.clone();
+        board.perft_inner(depth)
+    }
+
+    // Like perft, but split by the first move, which makes it easy to find
+    // the move a wrong count comes from by comparing with another program.
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut board = self.clone();
+        board.legal_moves().into_iter().map(|mv| {
+            let undo = board.make_move_unchecked(mv);
+            let nodes = board.perft_inner(depth - 1);
+            board.unmake_move(mv, undo);
+            (mv, nodes)
+        }).collect()
+    }
+
+    fn perft_inner(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            let undo = self.make_move_unchecked(mv);
+            nodes += self.perft_inner(depth - 1);
+            self.unmake_move(mv, undo);
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(fen: &str, depth: u32) -> u64 {
+        Board::new_from_fen(String::from(fen)).expect("").perft(depth)
+    }
+
+    // Positions and counts from https://www.chessprogramming.org/Perft_Results
+
+    #[test]
+    fn test_perft_start_position() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(perft(fen, 0), 1);
+        assert_eq!(perft(fen, 1), 20);
+        assert_eq!(perft(fen, 2), 400);
+        assert_eq!(perft(fen, 3), 8902);
+    }
+
+    #[test]
+    fn test_perft_kiwipete() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft(fen, 1), 48);
+        assert_eq!(perft(fen, 2), 2039);
+        assert_eq!(perft(fen, 3), 97862);
+    }
+
+    #[test]
+    fn test_perft_position_3() {
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        assert_eq!(perft(fen, 1), 14);
+        assert_eq!(perft(fen, 2), 191);
+        assert_eq!(perft(fen, 3), 2812);
+        assert_eq!(perft(fen, 4), 43238);
+    }
+
+    #[test]
+    fn test_perft_position_4() {
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_eq!(perft(fen, 1), 6);
+        assert_eq!(perft(fen, 2), 264);
+        assert_eq!(perft(fen, 3), 9467);
+    }
+
+    #[test]
+    fn test_perft_position_5() {
+        let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+        assert_eq!(perft(fen, 1), 44);
+        assert_eq!(perft(fen, 2), 1486);
+        assert_eq!(perft(fen, 3), 62379);
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let board = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).expect("");
+        let divide = board.perft_divide(2);
+        assert_eq!(divide.len(), 20);
+        assert!(divide.iter().all(|(_, nodes)| *nodes == 20));
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), board.perft(2));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::castling::CastlingRights;
+use crate::square::Square;
+
+use super::{Board, PieceColor, PieceType};
+
+// Zobrist hashing: every piece on every square, the side to move, each set
+// of castling rights and each en passant file gets a random 64 bit key, and
+// a position's hash is the xor of the keys that apply to it. Xor undoes
+// itself, so a move only has to xor in and out the keys it changes, which
+// is what makes the hash cheap enough for search and repetition checks.
+// Unlike position_id it isn't meant to be stored: the keys may change.
+
+// xorshift64*, run at compile time to fill in the key tables.
+const fn next_key(state: u64) -> u64 {
+    let mut x = state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    x
+}
+
+const fn scramble(state: u64) -> u64 {
+    state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+struct Keys {
+    // Indexed by PieceType as usize * 2 + PieceColor as usize, then square.
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    // Indexed by CastlingRights::bits.
+    castling: [u64; 16],
+    en_passant: [u64; 8],
+}
+
+const fn generate_keys() -> Keys {
+    let mut keys = Keys { pieces: [[0; 64]; 12], black_to_move: 0, castling: [0; 16], en_passant: [0; 8] };
+    let mut state = 0x9e37_79b9_7f4a_7c15;
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            state = next_key(state);
+            keys.pieces[piece][square] = scramble(state);
+            square += 1;
+        }
+        piece += 1;
+    }
+    state = next_key(state);
+    keys.black_to_move = scramble(state);
+    // No rights at all gets no key, so a position without castling or en
+    // passant hashes to just its pieces and side to move.
+    let mut rights = 1;
+    while rights < 16 {
+        state = next_key(state);
+        keys.castling[rights] = scramble(state);
+        rights += 1;
+    }
+    let mut file = 0;
+    while file < 8 {
+        state = next_key(state);
+        keys.en_passant[file] = scramble(state);
+        file += 1;
+    }
+    keys
+}
+
+const KEYS: Keys = generate_keys();
+
+pub(super) fn piece_key(square: Square, ptype: PieceType, color: PieceColor) -> u64 {
+    KEYS.pieces[ptype as usize * 2 + color as usize][square.index()]
+}
+
+// The keys for everything but the pieces.
+fn state_key(side: PieceColor, castling: CastlingRights, en_passant: Option<Square>) -> u64 {
+    let mut key = KEYS.castling[castling.bits() as usize];
+    if side == PieceColor::Black {
+        key ^= KEYS.black_to_move;
+    }
+    if let Some(square) = en_passant {
+        key ^= KEYS.en_passant[square.x() as usize];
+    }
+    key
+}
+
+impl Board {
+    // A hash of the piece placement, side to move, castling rights and en
+    // passant file, kept up to date as moves are made and taken back. The en
+    // passant file counts whenever there is an en passant square, whether or
+    // not a pawn can capture there.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.zobrist
+    }
+
+    // The hash worked out from scratch.
+    pub(super) fn compute_zobrist(&self) -> u64 {
+        let mut hash = self.state_key();
+        for (index, piece) in self.mailbox.iter().enumerate() {
+            if let Some((ptype, color)) = piece {
+                hash ^= piece_key(Square::from_index(index as u8), *ptype, *color);
+            }
+        }
+        hash
+    }
+
+    // The hash's keys for the side to move, castling rights and en passant
+    // square. Code that changes those xors this out before and back in after.
+    pub(super) fn state_key(&self) -> u64 {
+        state_key(self.current_move, self.castling_rights, self.en_passant_square)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::Move;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    // Walks the move tree checking the incremental hash against one worked
+    // out from scratch after every make and unmake.
+    fn check_tree(board: &mut Board, depth: u32) {
+        assert_eq!(board.zobrist_hash(), board.compute_zobrist());
+        if depth == 0 {
+            return;
+        }
+        for mv in board.legal_moves() {
+            let before = board.zobrist_hash();
+            let undo = board.make_move_unchecked(mv);
+            check_tree(board, depth - 1);
+            board.unmake_move(mv, undo);
+            assert_eq!(board.zobrist_hash(), before);
+        }
+    }
+
+    #[test]
+    fn test_incremental_hash() {
+        // Castling, en passant and promotions all within a couple of moves.
+        check_tree(&mut board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"), 2);
+        check_tree(&mut board("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"), 2);
+        check_tree(&mut board("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"), 3);
+    }
+
+    #[test]
+    fn test_transpositions() {
+        let play = |moves: &[&str]| {
+            let mut board = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+            for uci in moves {
+                board.make_move(Move::from_uci(uci).unwrap()).expect("");
+            }
+            board
+        };
+        let a = play(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let b = play(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert_eq!(a.zobrist_hash(), b.zobrist_hash());
+        // The same pieces, but with the other side to move, the castling
+        // rights gone or an en passant square, are different positions.
+        let knights_back = play(&["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(knights_back.zobrist_hash(), board(crate::game::STARTING_FEN).zobrist_hash());
+        let rooks_back = play(&["h2h3", "h7h6", "h1h2", "h8h7", "h2h1", "h7h8"]);
+        let played_rooks = play(&["h2h3", "h7h6"]);
+        assert_eq!(rooks_back.to_fen().split(' ').next(), played_rooks.to_fen().split(' ').next());
+        assert_ne!(rooks_back.zobrist_hash(), played_rooks.zobrist_hash());
+        assert_ne!(
+            board("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").zobrist_hash(),
+            board("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").zobrist_hash()
+        );
+        assert_ne!(
+            board("4k3/8/8/8/8/8/8/4K3 w - - 0 1").zobrist_hash(),
+            board("4k3/8/8/8/8/8/8/4K3 b - - 0 1").zobrist_hash()
+        );
+    }
+
+    #[test]
+    fn test_edits_keep_hash() {
+        let mut board = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        board.set_side_to_move(PieceColor::Black).expect("");
+        board.clear_square(Square::new(0, 7)).expect("");
+        board.set_castling(CastlingRights::NONE).expect("");
+        assert_eq!(board.zobrist_hash(), board.compute_zobrist());
+        assert!(board.clear_square(Square::new(4, 0)).is_err());
+        assert_eq!(board.zobrist_hash(), board.compute_zobrist());
+    }
+}
@@ -0,0 +1,22 @@
+pub mod advisor;
+pub mod analysis;
+pub mod bitboard;
+pub mod castling;
+pub mod clock;
+pub mod epd;
+pub mod eval;
+pub mod game;
+mod json;
+pub mod moves;
+#[cfg(feature = "online")]
+pub mod online;
+pub mod outcome;
+pub mod pgn;
+pub mod position_id;
+pub mod prelude;
+pub mod puzzle;
+pub mod puzzle_db;
+pub mod query;
+pub mod rating;
+pub mod san;
+pub mod square;
@@ -1,9 +1,8 @@
-mod game;
-use crate::game::Board;
+use chess_rust::prelude::*;
 
 fn main() {
     println!("Hello, world!");
 
     let board = Board::new_from_fen(String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")).expect("");
-
+    board.render();
 }
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::game::PieceType;
+use crate::square::Square;
+
+// A move of one piece from a square to another. Captures aren't marked
+// specially; a move is a capture when the destination holds an enemy piece.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+
+    // What a pawn reaching the last rank turns into. None for every other move.
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square) -> Move {
+        Move { from, to, promotion: None }
+    }
+
+    pub fn new_promotion(from: Square, to: Square, promotion: PieceType) -> Move {
+        Move { from, to, promotion: Some(promotion) }
+    }
+
+    // Parses a move in the long algebraic form used by UCI, e.g. "e2e4" or
+    // "e7e8q" for a promotion.
+    pub fn from_uci(s: &str) -> Result<Move, String> {
+        if !s.is_ascii() || (s.len() != 4 && s.len() != 5) {
+            return Err(format!("Invalid UCI move \"{}\".", s));
+        }
+        let from: Square = s[0..2].parse()?;
+        let to: Square = s[2..4].parse()?;
+        let promotion = match s[4..].chars().next() {
+            None => None,
+            Some('q') => Some(PieceType::Queen),
+            Some('r') => Some(PieceType::Rook),
+            Some('b') => Some(PieceType::Bishop),
+            Some('n') => Some(PieceType::Knight),
+            Some(_) => return Err(format!("Invalid promotion piece in UCI move \"{}\".", s)),
+        };
+        Ok(Move { from, to, promotion })
+    }
+
+    pub fn to_uci(&self) -> String {
+        let promotion = match self.promotion {
+            None => "",
+            Some(PieceType::Queen) => "q",
+            Some(PieceType::Rook) => "r",
+            Some(PieceType::Bishop) => "b",
+            Some(PieceType::Knight) => "n",
+            Some(PieceType::Pawn) => "p",
+            Some(PieceType::King) => "k",
+        };
+        format!("{}{}{}", self.from, self.to, promotion)
+    }
+}
+
+// Why Board::make_move or Game::play refused a move.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum MoveError {
+    // There is no piece on the from square.
+    NoPiece,
+    // The piece on the from square belongs to the side not to move.
+    WrongColor,
+    // The piece can't move there, or doing so would leave the king in check.
+    Illegal,
+    // The game already has a result, so no more moves can be played in it.
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::NoPiece => write!(f, "There is no piece on that square."),
+            MoveError::WrongColor => write!(f, "That piece belongs to the other side."),
+            MoveError::Illegal => write!(f, "That move is not legal."),
+            MoveError::GameOver => write!(f, "The game is already over."),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uci() {
+        let mv = Move::from_uci("e2e4").expect("");
+        assert_eq!(mv, Move::new(Square::new(4, 6), Square::new(4, 4)));
+        assert_eq!(mv.to_uci(), "e2e4");
+
+        let promotion = Move::from_uci("a7a8n").expect("");
+        assert_eq!(promotion, Move::new_promotion(Square::new(0, 1), Square::new(0, 0), PieceType::Knight));
+        assert_eq!(promotion.to_uci(), "a7a8n");
+        assert_eq!(Move::from_uci("h2h1q").expect("").to_uci(), "h2h1q");
+
+        assert!(Move::from_uci("e2e").is_err());
+        assert!(Move::from_uci("e2e9").is_err());
+        assert!(Move::from_uci("e7e8k").is_err());
+        assert!(Move::from_uci("e7e8qq").is_err());
+        assert!(Move::from_uci("0000").is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+
+use crate::game::{Board, PieceColor, STARTING_FEN};
+use crate::json::{Json, JsonError, JsonParser};
+use crate::moves::MoveError;
+use crate::outcome::{Outcome, Termination};
+use crate::pgn::{Game, PgnError};
+
+// Importing games from the lichess and chess.com APIs. lichess exports a game
+// as a JSON object with its moves in SAN, or as one such object per line when
+// downloading many games. chess.com's monthly archives are a JSON object
+// holding a list of games, each with its PGN.
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum OnlineError {
+    // The text isn't valid JSON.
+    BadJson,
+    // A field the game can't be built without is missing or has the wrong type.
+    MissingField(&'static str),
+    // A variant other than standard chess.
+    UnsupportedVariant(String),
+    // A move that is legal in its position but can't be added to the game,
+    // because the rules had already ended it.
+    BadMove { ply: usize, error: MoveError },
+    Pgn(PgnError),
+}
+
+impl fmt::Display for OnlineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OnlineError::BadJson => write!(f, "Invalid JSON."),
+            OnlineError::MissingField(name) => write!(f, "Missing or invalid field \"{}\".", name),
+            OnlineError::UnsupportedVariant(name) => write!(f, "Unsupported variant \"{}\".", name),
+            OnlineError::BadMove { ply, error } => write!(f, "Can't play the move at ply {}: {}", ply + 1, error),
+            OnlineError::Pgn(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for OnlineError {}
+
+impl From<JsonError> for OnlineError {
+    fn from(_: JsonError) -> OnlineError {
+        OnlineError::BadJson
+    }
+}
+
+impl From<PgnError> for OnlineError {
+    fn from(e: PgnError) -> OnlineError {
+        OnlineError::Pgn(e)
+    }
+}
+
+// Turns milliseconds since the epoch into a PGN date, "YYYY.MM.DD".
+fn pgn_date(millis: f64) -> String {
+    // Howard Hinnant's days to civil date algorithm.
+    let days = (millis / 86_400_000.0).floor() as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// A lichess player is a user, an anonymous player or the computer.
+fn lichess_player_name(player: &Json) -> String {
+    if let Some(name) = player.get("user").and_then(|u| u.get("name")).and_then(Json::as_str) {
+        return String::from(name);
+    }
+    match player.get("aiLevel").and_then(Json::as_f64) {
+        Some(level) => format!("lichess AI level {}", level),
+        None => String::from("Anonymous"),
+    }
+}
+
+fn lichess_game(json: &Json) -> Result<Game, OnlineError> {
+    let variant = json.get("variant").and_then(Json::as_str).unwrap_or("standard");
+    if variant != "standard" && variant != "fromPosition" {
+        return Err(OnlineError::UnsupportedVariant(String::from(variant)));
+    }
+    let fen = json.get("initialFen").and_then(Json::as_str).unwrap_or(STARTING_FEN);
+    let board = Board::new_from_fen(String::from(fen)).map_err(|e| OnlineError::Pgn(PgnError::BadFen(e)))?;
+    let mut game = Game::new(board);
+
+    let rated = json.get("rated") == Some(&Json::Bool(true));
+    let speed = json.get("speed").and_then(Json::as_str).unwrap_or("casual");
+    game.set_event(&format!("{} {} game", if rated { "Rated" } else { "Casual" }, speed));
+    if let Some(id) = json.get("id").and_then(Json::as_str) {
+        game.set_site(&format!("https://lichess.org/{}", id));
+    }
+    if let Some(created) = json.get("createdAt").and_then(Json::as_f64) {
+        game.set_date(&pgn_date(created));
+    }
+    let players = json.get("players").ok_or(OnlineError::MissingField("players"))?;
+    for (color, name_tag, elo_tag) in [("white", "White", "WhiteElo"), ("black", "Black", "BlackElo")].iter() {
+        let player = players.get(color).ok_or(OnlineError::MissingField("players"))?;
+        game.set_tag(name_tag, &lichess_player_name(player));
+        if let Some(rating) = player.get("rating").and_then(Json::as_f64) {
+            game.set_tag(elo_tag, &rating.to_string());
+        }
+    }
+    if let Some(opening) = json.get("opening") {
+        if let Some(eco) = opening.get("eco").and_then(Json::as_str) {
+            game.set_tag("ECO", eco);
+        }
+        if let Some(name) = opening.get("name").and_then(Json::as_str) {
+            game.set_tag("Opening", name);
+        }
+    }
+    if fen != STARTING_FEN {
+        game.set_tag("SetUp", "1");
+        game.set_tag("FEN", fen);
+    }
+
+    let moves = json.get("moves").and_then(Json::as_str).ok_or(OnlineError::MissingField("moves"))?;
+    for (ply, san) in moves.split_whitespace().enumerate() {
+        let mv = game.board().move_from_san(san).map_err(|error| PgnError::BadMove {
+            ply,
+            san: String::from(san),
+            error,
+        })?;
+        game.play(mv).map_err(|error| OnlineError::BadMove { ply, error })?;
+    }
+
+    let status = json.get("status").and_then(Json::as_str).unwrap_or("started");
+    let winner = match json.get("winner").and_then(Json::as_str) {
+        Some("white") => Some(PieceColor::White),
+        Some("black") => Some(PieceColor::Black),
+        _ => None,
+    };
+    match lichess_termination(status) {
+        Some(termination) => game.set_outcome(match winner {
+            Some(color) => Outcome::win(color, termination),
+            None => Outcome::Draw(termination),
+        }),
+        // Statuses that don't say how the game ended, like "draw", which
+        // covers agreements as well as claimed repetitions, are left to
+        // Game::outcome to work out from the final position.
+        None => game.set_result(match winner {
+            Some(color) => Outcome::win(color, Termination::Resignation).result(),
+            None if status == "created" || status == "started" || status == "aborted" => "*",
+            None => "1/2-1/2",
+        }),
+    }
+    Ok(game)
+}
+
+// How a game with this lichess status ended, where the status says.
+// "timeout" is lichess's name for a player leaving the game, which is
+// scored like a resignation.
+fn lichess_termination(status: &str) -> Option<Termination> {
+    match status {
+        "mate" => Some(Termination::Checkmate),
+        "resign" | "timeout" => Some(Termination::Resignation),
+        "outoftime" => Some(Termination::Timeout),
+        "stalemate" => Some(Termination::Stalemate),
+        "insufficientMaterialClaim" => Some(Termination::InsufficientMaterial),
+        _ => None,
+    }
+}
+
+// Reads one game from the lichess game export API in JSON form.
+pub fn game_from_lichess_json(text: &str) -> Result<Game, OnlineError> {
+    lichess_game(&JsonParser::parse(text)?)
+}
+
+// Reads a lichess user game download in NDJSON form, one game per line. A
+// game that can't be read doesn't stop the others from being read.
+pub fn games_from_lichess_ndjson(text: &str) -> Vec<Result<Game, OnlineError>> {
+    text.lines().filter(|line| !line.trim().is_empty()).map(game_from_lichess_json).collect()
+}
+
+// Reads a chess.com monthly archive, as returned by
+// https://api.chess.com/pub/player/{user}/games/{year}/{month}. Games of
+// other variants come back as UnsupportedVariant errors.
+pub fn games_from_chess_com_archive(text: &str) -> Result<Vec<Result<Game, OnlineError>>, OnlineError> {
+    let json = JsonParser::parse(text)?;
+    let games = match json.get("games") {
+        Some(Json::Array(games)) => games,
+        _ => return Err(OnlineError::MissingField("games")),
+    };
+    Ok(games
+        .iter()
+        .map(|game| {
+            let rules = game.get("rules").and_then(Json::as_str).unwrap_or("chess");
+            if rules != "chess" {
+                return Err(OnlineError::UnsupportedVariant(String::from(rules)));
+            }
+            let pgn = game.get("pgn").and_then(Json::as_str).ok_or(OnlineError::MissingField("pgn"))?;
+            Ok(Game::from_pgn(pgn)?)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LICHESS: &str = r#"{"id":"q7ZvsdUF","rated":true,"variant":"standard","speed":"blitz","perf":"blitz",
+        "createdAt":1514505150384,"lastMoveAt":1514505592843,"status":"mate",
+        "players":{"white":{"user":{"name":"Lance5500","id":"lance5500"},"rating":2389,"ratingDiff":4},
+                   "black":{"user":{"name":"TryingHard87","id":"tryinghard87"},"rating":2498,"ratingDiff":-4}},
+        "winner":"white","opening":{"eco":"C20","name":"King's Pawn Game: Wayward Queen Attack","ply":3},
+        "moves":"e4 e5 Bc4 Nc6 Qh5 Nf6 Qxf7#","clock":{"initial":300,"increment":3,"totalTime":420}}"#;
+
+    #[test]
+    fn test_pgn_date() {
+        assert_eq!(pgn_date(0.0), "1970.01.01");
+        assert_eq!(pgn_date(1_514_505_150_384.0), "2017.12.28");
+        assert_eq!(pgn_date(951_782_400_000.0), "2000.02.29");
+    }
+
+    #[test]
+    fn test_lichess_game() {
+        let game = game_from_lichess_json(LICHESS).expect("");
+        assert_eq!(game.tag("White"), Some("Lance5500"));
+        assert_eq!(game.tag("BlackElo"), Some("2498"));
+        assert_eq!(game.tag("Site"), Some("https://lichess.org/q7ZvsdUF"));
+        assert_eq!(game.tag("Date"), Some("2017.12.28"));
+        assert_eq!(game.tag("Event"), Some("Rated blitz game"));
+        assert_eq!(game.tag("ECO"), Some("C20"));
+        assert_eq!(game.result(), "1-0");
+        assert_eq!(game.outcome(), Some(Outcome::WhiteWins(Termination::Checkmate)));
+        assert_eq!(game.san_moves().len(), 7);
+        assert!(game.board().is_checkmate());
+
+        let games = games_from_lichess_ndjson(&format!("{}\n\n{}\n", LICHESS.replace('\n', ""), r#"{"variant":"atomic"}"#));
+        assert_eq!(games.len(), 2);
+        assert!(games[0].is_ok());
+        assert_eq!(games[1].as_ref().unwrap_err(), &OnlineError::UnsupportedVariant(String::from("atomic")));
+    }
+
+    #[test]
+    fn test_lichess_errors() {
+        assert_eq!(game_from_lichess_json("{").unwrap_err(), OnlineError::BadJson);
+        let no_moves = r#"{"players":{"white":{},"black":{"aiLevel":3}}}"#;
+        assert_eq!(game_from_lichess_json(no_moves).unwrap_err(), OnlineError::MissingField("moves"));
+        let bad_move = r#"{"players":{"white":{},"black":{"aiLevel":3}},"moves":"e4 e4","status":"started"}"#;
+        match game_from_lichess_json(bad_move) {
+            Err(OnlineError::Pgn(PgnError::BadMove { ply: 1, .. })) => {}
+            other => panic!("{:?}", other),
+        }
+        let ok = r#"{"players":{"white":{},"black":{"aiLevel":3}},"moves":"e4","status":"started"}"#;
+        let game = game_from_lichess_json(ok).expect("");
+        assert_eq!(game.tag("White"), Some("Anonymous"));
+        assert_eq!(game.tag("Black"), Some("lichess AI level 3"));
+        assert_eq!(game.result(), "*");
+    }
+
+    #[test]
+    fn test_lichess_status() {
+        let outcome = |status: &str, winner: &str| {
+            let json = format!(
+                r#"{{"players":{{"white":{{}},"black":{{}}}},"moves":"e4 e5 Nf3","status":"{}"{}}}"#,
+                status, winner
+            );
+            game_from_lichess_json(&json).expect("").outcome()
+        };
+        let black = r#","winner":"black""#;
+        assert_eq!(outcome("outoftime", black), Some(Outcome::BlackWins(Termination::Timeout)));
+        assert_eq!(outcome("outoftime", ""), Some(Outcome::Draw(Termination::Timeout)));
+        assert_eq!(outcome("resign", black), Some(Outcome::BlackWins(Termination::Resignation)));
+        assert_eq!(outcome("timeout", black), Some(Outcome::BlackWins(Termination::Resignation)));
+        assert_eq!(outcome("draw", ""), Some(Outcome::Draw(Termination::Agreement)));
+        assert_eq!(outcome("started", ""), None);
+
+        // Taking the last rook ends the game, so the move after it can't be
+        // played.
+        let after_end = r#"{"players":{"white":{},"black":{}},"initialFen":"8/8/8/8/8/8/1r6/K6k w - - 0 1",
+            "moves":"Kxb2 Kh2","status":"draw"}"#;
+        assert_eq!(
+            game_from_lichess_json(after_end).unwrap_err(),
+            OnlineError::BadMove { ply: 1, error: MoveError::GameOver }
+        );
+    }
+
+    #[test]
+    fn test_chess_com_archive() {
+        let archive = r#"{"games":[
+            {"url":"https://www.chess.com/game/live/1","rules":"chess",
+             "pgn":"[Event \"Live Chess\"]\n[White \"a\"]\n[Black \"b\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1"},
+            {"url":"https://www.chess.com/game/live/2","rules":"chess960","pgn":"1. e4 *"},
+            {"url":"https://www.chess.com/game/live/3","rules":"chess"}
+        ]}"#;
+        let games = games_from_chess_com_archive(archive).expect("");
+        assert_eq!(games.len(), 3);
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.result(), "0-1");
+        assert_eq!(first.tag("Black"), Some("b"));
+        assert!(first.board().is_checkmate());
+        assert_eq!(games[1].as_ref().unwrap_err(), &OnlineError::UnsupportedVariant(String::from("chess960")));
+        assert_eq!(games[2].as_ref().unwrap_err(), &OnlineError::MissingField("pgn"));
+        assert_eq!(games_from_chess_com_archive("[]").unwrap_err(), OnlineError::MissingField("games"));
+    }
+}
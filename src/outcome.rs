@@ -0,0 +1,77 @@
+use crate::game::PieceColor;
+
+// How a finished game ended.
+
+// Why the game ended.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Resignation,
+    // A player ran out of time.
+    Timeout,
+    Repetition,
+    // Fifty (claimed) or seventy-five moves without a capture or pawn move.
+    FiftyMove,
+    InsufficientMaterial,
+    // The players agreed to a draw.
+    Agreement,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum Outcome {
+    WhiteWins(Termination),
+    BlackWins(Termination),
+    Draw(Termination),
+}
+
+impl Outcome {
+    // A win for `color`.
+    pub fn win(color: PieceColor, termination: Termination) -> Outcome {
+        match color {
+            PieceColor::White => Outcome::WhiteWins(termination),
+            PieceColor::Black => Outcome::BlackWins(termination),
+        }
+    }
+
+    pub fn termination(self) -> Termination {
+        match self {
+            Outcome::WhiteWins(t) | Outcome::BlackWins(t) | Outcome::Draw(t) => t,
+        }
+    }
+
+    // None for a draw.
+    pub fn winner(self) -> Option<PieceColor> {
+        match self {
+            Outcome::WhiteWins(_) => Some(PieceColor::White),
+            Outcome::BlackWins(_) => Some(PieceColor::Black),
+            Outcome::Draw(_) => None,
+        }
+    }
+
+    // The result as written in PGN: "1-0", "0-1" or "1/2-1/2".
+    pub fn result(self) -> &'static str {
+        match self {
+            Outcome::WhiteWins(_) => "1-0",
+            Outcome::BlackWins(_) => "0-1",
+            Outcome::Draw(_) => "1/2-1/2",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome() {
+        let mate = Outcome::win(PieceColor::Black, Termination::Checkmate);
+        assert_eq!(mate, Outcome::BlackWins(Termination::Checkmate));
+        assert_eq!(mate.winner(), Some(PieceColor::Black));
+        assert_eq!(mate.result(), "0-1");
+        let draw = Outcome::Draw(Termination::Agreement);
+        assert_eq!(draw.winner(), None);
+        assert_eq!(draw.termination(), Termination::Agreement);
+        assert_eq!(draw.result(), "1/2-1/2");
+    }
+}
@@ -0,0 +1,1840 @@
+use std::fmt;
+use std::str::FromStr;
+use std::io::{self, BufRead, BufReader, Read};
+use std::time::Duration;
+
+use crate::clock::{self, Clock, TimeControl};
+use crate::game::{Board, FenError, PieceColor, PieceType, STARTING_FEN};
+use crate::moves::{Move, MoveError};
+use crate::outcome::{Outcome, Termination};
+use crate::position_id::PositionId;
+use crate::san::SanError;
+
+// Reading and writing games in Portable Game Notation, the format lichess and
+// most other sites export games in.
+
+// The tags every PGN game must have, in the order they are written.
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+// Export lines are kept at most this long.
+const LINE_LENGTH: usize = 80;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum PgnError {
+    // A tag pair that isn't written like [Name "value"].
+    BadTag,
+    // A comment or tag value that is never closed.
+    Unterminated,
+    // A ")" without a matching "(", or the other way round.
+    UnbalancedVariation,
+    // The FEN tag doesn't hold a valid position.
+    BadFen(FenError),
+    // A move that can't be played. Ply counts half moves from the start of
+    // the game, starting at 0.
+    BadMove { ply: usize, san: String, error: SanError },
+    // Reading the input failed.
+    Io(io::ErrorKind),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::BadTag => write!(f, "Invalid PGN tag pair."),
+            PgnError::Unterminated => write!(f, "Unterminated PGN comment or string."),
+            PgnError::UnbalancedVariation => write!(f, "Unbalanced parentheses in PGN variation."),
+            PgnError::BadFen(e) => write!(f, "Invalid FEN tag: {}", e),
+            PgnError::BadMove { ply, san, error } => write!(f, "Invalid move \"{}\" at ply {}: {}", san, ply, error),
+            PgnError::Io(kind) => write!(f, "Error reading PGN: {}", io::Error::from(*kind)),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Result(String),
+    Comment(String),
+    Nag(u8),
+    StartVariation,
+    EndVariation,
+}
+
+// Characters that end a move, move number or result symbol.
+fn ends_symbol(c: char) -> bool {
+    c.is_whitespace() || "{}()[];$".contains(c)
+}
+
+fn suffix_nag(annotation: &str) -> Option<u8> {
+    match annotation {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn is_result(s: &str) -> bool {
+    s == "1-0" || s == "0-1" || s == "1/2-1/2" || s == "*"
+}
+
+// Reads a [Name "value"] tag pair. The opening bracket has been consumed.
+fn read_tag(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Token, PgnError> {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if !(c.is_alphanumeric() || c == '_') {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    if name.is_empty() || chars.next() != Some('"') {
+        return Err(PgnError::BadTag);
+    }
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(PgnError::Unterminated),
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+                None => return Err(PgnError::Unterminated),
+                Some(c) => value.push(c),
+            },
+            Some(c) => value.push(c),
+        }
+    }
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    if chars.next() != Some(']') {
+        return Err(PgnError::BadTag);
+    }
+    Ok(Token::Tag(name, value))
+}
+
+fn tokenize(pgn: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        let was_line_start = line_start;
+        line_start = c == '\n';
+        match c {
+            // Byte order marks are left at the start of some exported files.
+            _ if c.is_whitespace() || c == '\u{feff}' => {}
+            // A line starting with % is an escape for other software.
+            '%' if was_line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line_start = true;
+                        break;
+                    }
+                }
+            }
+            '[' => tokens.push(read_tag(&mut chars)?),
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(PgnError::Unterminated),
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                    }
+                }
+                tokens.push(Token::Comment(String::from(comment.trim())));
+            }
+            ';' => {
+                let mut comment = String::new();
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line_start = true;
+                        break;
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(String::from(comment.trim())));
+            }
+            '(' => tokens.push(Token::StartVariation),
+            ')' => tokens.push(Token::EndVariation),
+            '$' => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                }
+                // Unknown or out of range NAGs are dropped.
+                if let Ok(nag) = digits.parse() {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+            _ => {
+                let mut symbol = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if ends_symbol(c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    chars.next();
+                }
+                if is_result(&symbol) {
+                    tokens.push(Token::Result(symbol));
+                    continue;
+                }
+                // Move numbers, either on their own ("12." or "12...") or
+                // stuck to the move ("12.Nf3").
+                let rest = match symbol.find('.') {
+                    Some(i) if symbol[..i].chars().all(|c| c.is_ascii_digit()) => symbol[i..].trim_start_matches('.'),
+                    _ => symbol.as_str(),
+                };
+                // Move suffix annotations, attached ("e4!") or not ("e4 !"),
+                // become the matching NAG.
+                let annotation_start = rest.trim_end_matches(['!', '?']).len();
+                let (mv, annotation) = rest.split_at(annotation_start);
+                if !mv.is_empty() {
+                    tokens.push(Token::Move(String::from(mv)));
+                }
+                if let Some(nag) = suffix_nag(annotation) {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// One move in a game tree, with everything that follows it.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct GameNode {
+    pub mv: Move,
+    pub san: String,
+    // Numeric annotation glyphs, e.g. 1 for "!" or 14 for "+=".
+    pub nags: Vec<u8>,
+    // A comment written just before the move. Only the first move of a
+    // variation usually has one.
+    pub starting_comment: Option<String>,
+    // The comment after the move.
+    pub comment: Option<String>,
+    // The moves that can follow this one. The first is the main line and
+    // the rest are variations.
+    pub children: Vec<GameNode>,
+}
+
+impl GameNode {
+    fn new(mv: Move, san: String) -> GameNode {
+        GameNode { mv, san, nags: Vec::new(), starting_comment: None, comment: None, children: Vec::new() }
+    }
+
+    // The mover's time left after the move, from a [%clk h:mm:ss] command
+    // in the comment.
+    pub fn clock(&self) -> Option<Duration> {
+        let comment = self.comment.as_ref()?;
+        let start = comment.find("[%clk ")? + "[%clk ".len();
+        let end = start + comment[start..].find(']')?;
+        clock::parse_clk(comment[start..end].trim())
+    }
+}
+
+fn append_comment(comment: &mut Option<String>, text: String) {
+    match comment {
+        None => *comment = Some(text),
+        Some(c) => {
+            c.push(' ');
+            c.push_str(&text);
+        }
+    }
+}
+
+// Builds the move tree from the movetext tokens.
+struct TreeParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // A comment before the first move of the game.
+    comment: Option<String>,
+    result: Option<String>,
+}
+
+impl TreeParser {
+    // Reads one line of moves, starting from `board` at `ply`, up to the end
+    // of the variation or the game. Returns the line's first move followed by
+    // any alternatives to it, with the rest of the line hanging off the first.
+    fn parse_line(&mut self, board: &Board, ply: usize, in_variation: bool) -> Result<Vec<GameNode>, PgnError> {
+        let mut line: Vec<GameNode> = Vec::new();
+        // For each move in the line, the alternatives given for it.
+        let mut alternatives: Vec<Vec<GameNode>> = Vec::new();
+        // The position before each move in the line, and after the last one.
+        let mut boards = vec![board.clone()];
+        let mut pending_comment = None;
+
+        while self.pos < self.tokens.len() {
+            let token = self.tokens[self.pos].clone();
+            self.pos += 1;
+            match token {
+                Token::Move(text) => {
+                    let before = boards.last().unwrap();
+                    let mv = move_from_notation(before, &text).map_err(|error| PgnError::BadMove {
+                        ply: ply + line.len(),
+                        san: text.clone(),
+                        error,
+                    })?;
+                    let mut node = GameNode::new(mv, mv.to_san(before));
+                    node.starting_comment = pending_comment.take();
+                    let mut after = before.clone();
+                    after.make_move_unchecked(mv);
+                    boards.push(after);
+                    line.push(node);
+                    alternatives.push(Vec::new());
+                }
+                Token::Comment(text) => match line.last_mut() {
+                    Some(node) => append_comment(&mut node.comment, text),
+                    None if in_variation => append_comment(&mut pending_comment, text),
+                    None => append_comment(&mut self.comment, text),
+                },
+                Token::Nag(nag) => {
+                    if let Some(node) = line.last_mut() {
+                        node.nags.push(nag);
+                    }
+                }
+                Token::StartVariation => {
+                    // A variation replaces the last move played.
+                    if line.is_empty() {
+                        return Err(PgnError::UnbalancedVariation);
+                    }
+                    let n = line.len() - 1;
+                    let variation = self.parse_line(&boards[n], ply + n, true)?;
+                    alternatives[n].extend(variation);
+                }
+                Token::EndVariation => {
+                    if !in_variation {
+                        return Err(PgnError::UnbalancedVariation);
+                    }
+                    return Ok(Self::fold(line, alternatives));
+                }
+                Token::Result(r) => {
+                    if !in_variation {
+                        self.result = Some(r);
+                        break;
+                    }
+                }
+                Token::Tag(..) => {}
+            }
+        }
+        if in_variation {
+            return Err(PgnError::UnbalancedVariation);
+        }
+        Ok(Self::fold(line, alternatives))
+    }
+
+    // Turns a line of moves into a chain, each move holding the next one
+    // followed by that move's alternatives.
+    fn fold(line: Vec<GameNode>, alternatives: Vec<Vec<GameNode>>) -> Vec<GameNode> {
+        let mut next = Vec::new();
+        for (mut node, alts) in line.into_iter().zip(alternatives).rev() {
+            node.children = next;
+            next = vec![node];
+            next.extend(alts);
+        }
+        next
+    }
+}
+
+// A game read from PGN or built move by move: its tags, the tree of moves
+// and where the main line leads.
+#[derive(Debug, Clone)]
+pub struct Game {
+    // In the order they appeared.
+    tags: Vec<(String, String)>,
+    start: Board,
+    comment: Option<String>,
+    // The first moves of the game: the main line followed by variations.
+    nodes: Vec<GameNode>,
+    // The main line, kept alongside the tree so it can be borrowed as a slice.
+    board: Board,
+    moves: Vec<Move>,
+    san: Vec<String>,
+    result: String,
+    // Why the game ended, when that is known rather than worked out.
+    termination: Option<Termination>,
+    // Moves taken back with undo, the most recent last.
+    redo: Vec<GameNode>,
+    // Board::repetition_id of the start and of the position after each
+    // main line move.
+    history: Vec<PositionId>,
+    // The player whose draw offer is waiting for an answer.
+    draw_offer: Option<PieceColor>,
+    // Called after every move to end the game on the arbiter's terms.
+    adjudicator: Option<fn(&Game) -> Option<Outcome>>,
+    clock: Option<Clock>,
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Game {
+    // A game with no moves yet, starting from the given position.
+    pub fn new(start: Board) -> Game {
+        Game {
+            tags: Vec::new(),
+            history: vec![start.repetition_id()],
+            board: start.clone(),
+            start,
+            comment: None,
+            nodes: Vec::new(),
+            moves: Vec::new(),
+            san: Vec::new(),
+            result: String::from("*"),
+            termination: None,
+            redo: Vec::new(),
+            draw_offer: None,
+            adjudicator: None,
+            clock: None,
+        }
+    }
+
+    // The list holding the last main line move and its alternatives.
+    fn last_nodes(&mut self) -> &mut Vec<GameNode> {
+        let mut nodes = &mut self.nodes;
+        for _ in 1..self.moves.len() {
+            nodes = &mut nodes[0].children;
+        }
+        nodes
+    }
+
+    // Adds a move to the end of the main line. Anything that was there before
+    // an undo becomes a variation of it.
+    fn push_node(&mut self, node: GameNode) {
+        self.board.make_move_unchecked(node.mv);
+        self.history.push(self.board.repetition_id());
+        self.moves.push(node.mv);
+        self.san.push(node.san.clone());
+        self.last_nodes().insert(0, node);
+        self.update_result();
+    }
+
+    // The outcome the rules force on the game in its current position, if
+    // any. A fivefold repetition, 75 moves without progress or a position
+    // where neither side can mate are drawn without either player claiming
+    // it.
+    fn forced_outcome(&self) -> Option<Outcome> {
+        if self.board.is_checkmate() {
+            return Some(Outcome::win(self.board.side_to_move().opposite(), Termination::Checkmate));
+        }
+        let termination = if self.board.is_stalemate() {
+            Termination::Stalemate
+        } else if self.is_fivefold_repetition() {
+            Termination::Repetition
+        } else if self.board.is_seventy_five_move_draw() {
+            Termination::FiftyMove
+        } else if self.board.is_insufficient_material() {
+            Termination::InsufficientMaterial
+        } else {
+            return None;
+        };
+        Some(Outcome::Draw(termination))
+    }
+
+    fn update_result(&mut self) {
+        if let Some(outcome) = self.forced_outcome() {
+            self.set_outcome(outcome);
+        } else if let Some(outcome) = self.adjudicator.and_then(|adjudicator| adjudicator(self)) {
+            self.adjudicate(outcome);
+        }
+    }
+
+    fn check_unfinished(&self) -> Result<(), String> {
+        if self.is_finished() {
+            return Err(String::from("The game is already over."));
+        }
+        Ok(())
+    }
+
+    // Whether the game has a result. Games still being played, adjourned
+    // ones and ones whose result was never recorded all have "*".
+    pub fn is_finished(&self) -> bool {
+        self.result != "*"
+    }
+
+    // Stops the game to be finished later. The result stays "*" and the
+    // Termination tag is set to "unterminated".
+    pub fn adjourn(&mut self) -> Result<(), String> {
+        self.check_unfinished()?;
+        self.draw_offer = None;
+        self.set_tag("Termination", "unterminated");
+        Ok(())
+    }
+
+    pub fn is_adjourned(&self) -> bool {
+        !self.is_finished() && self.tag("Termination").is_some_and(|t| t.eq_ignore_ascii_case("unterminated"))
+    }
+
+    // Offers a draw on behalf of `color`. The offer stands until the other
+    // player accepts it, declines it or makes a move.
+    pub fn offer_draw(&mut self, color: PieceColor) -> Result<(), String> {
+        self.check_unfinished()?;
+        self.draw_offer = Some(color);
+        Ok(())
+    }
+
+    // The player whose draw offer hasn't been answered yet.
+    pub fn draw_offer(&self) -> Option<PieceColor> {
+        self.draw_offer
+    }
+
+    pub fn decline_draw(&mut self) {
+        self.draw_offer = None;
+    }
+
+    // Ends the game as a draw by agreement.
+    pub fn accept_draw(&mut self) -> Result<(), String> {
+        self.check_unfinished()?;
+        if self.draw_offer.take().is_none() {
+            return Err(String::from("No draw has been offered."));
+        }
+        self.set_outcome(Outcome::Draw(Termination::Agreement));
+        Ok(())
+    }
+
+    pub fn resign(&mut self, color: PieceColor) -> Result<(), String> {
+        self.check_unfinished()?;
+        self.draw_offer = None;
+        self.set_outcome(Outcome::win(color.opposite(), Termination::Resignation));
+        Ok(())
+    }
+
+    // Ends the game by an arbiter's decision, replacing any result it
+    // already had. The termination says why, and the Termination tag is set
+    // to "adjudication".
+    pub fn adjudicate(&mut self, outcome: Outcome) {
+        self.draw_offer = None;
+        self.set_outcome(outcome);
+        self.set_tag("Termination", "adjudication");
+    }
+
+    // Sets a function that is asked after every move whether the game
+    // should be adjudicated, e.g. to end games that go on too long. It isn't
+    // asked once the rules have ended the game.
+    pub fn set_adjudicator(&mut self, adjudicator: Option<fn(&Game) -> Option<Outcome>>) {
+        self.adjudicator = adjudicator;
+    }
+
+    // Ends the game. A timeout is also recorded in a Termination tag so it
+    // survives a trip through PGN.
+    pub fn set_outcome(&mut self, outcome: Outcome) {
+        self.set_result(outcome.result());
+        if outcome.termination() == Termination::Timeout {
+            self.set_tag("Termination", "time forfeit");
+        }
+        self.termination = Some(outcome.termination());
+    }
+
+    // How the game ended, or None if it hasn't. For games read from PGN, or
+    // whose result was set directly, the reason is worked out from the final
+    // position and the Termination tag: a decisive game that didn't end in
+    // mate or on time is taken as resigned, and a draw the rules don't
+    // explain as agreed.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let winner = match self.result.as_str() {
+            "1-0" => Some(PieceColor::White),
+            "0-1" => Some(PieceColor::Black),
+            "1/2-1/2" => None,
+            _ => return None,
+        };
+        let termination = self.termination.unwrap_or_else(|| self.inferred_termination(winner));
+        Some(match winner {
+            Some(color) => Outcome::win(color, termination),
+            None => Outcome::Draw(termination),
+        })
+    }
+
+    fn inferred_termination(&self, winner: Option<PieceColor>) -> Termination {
+        let timeout = self.tag("Termination").is_some_and(|t| t.eq_ignore_ascii_case("time forfeit"));
+        if self.board.is_checkmate() {
+            Termination::Checkmate
+        } else if self.board.is_stalemate() {
+            Termination::Stalemate
+        } else if timeout {
+            Termination::Timeout
+        } else if winner.is_some() {
+            Termination::Resignation
+        } else if self.board.is_insufficient_material() {
+            Termination::InsufficientMaterial
+        } else if self.is_threefold_repetition() {
+            Termination::Repetition
+        } else if self.board.is_fifty_move_draw() {
+            Termination::FiftyMove
+        } else {
+            Termination::Agreement
+        }
+    }
+
+    // How many times the current position has occurred in the main line,
+    // counting this time.
+    pub fn repetition_count(&self) -> usize {
+        let current = self.history.last().unwrap();
+        self.history.iter().filter(|&id| id == current).count()
+    }
+
+    // The position has occurred three times, so either player may claim a
+    // draw.
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    pub fn is_fivefold_repetition(&self) -> bool {
+        self.repetition_count() >= 5
+    }
+
+    // Plays a move at the end of the main line. Moves that were undone can
+    // no longer be redone. Fails once the game has a result.
+    pub fn play(&mut self, mv: Move) -> Result<(), MoveError> {
+        if self.is_finished() {
+            return Err(MoveError::GameOver);
+        }
+        self.board.clone().make_move(mv)?;
+        let san = mv.to_san(&self.board);
+        self.redo.clear();
+        if self.draw_offer != Some(self.board.side_to_move()) {
+            self.draw_offer = None;
+        }
+        self.push_node(GameNode::new(mv, san));
+        Ok(())
+    }
+
+    // Starts timing the game. Undoing moves doesn't turn the clock back.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = Some(clock);
+    }
+
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    // Plays a move that took `elapsed`, taking the time off the mover's
+    // clock and noting what they have left in a [%clk] comment. If their
+    // flag fell first the move isn't played and they lose on time. Without a
+    // clock this is the same as play. Once the game has a result the move
+    // is refused and the clock is left alone.
+    pub fn play_timed(&mut self, mv: Move, elapsed: Duration) -> Result<(), MoveError> {
+        if self.is_finished() {
+            return Err(MoveError::GameOver);
+        }
+        self.board.clone().make_move(mv)?;
+        let color = self.board.side_to_move();
+        let clock = match &mut self.clock {
+            None => return self.play(mv),
+            Some(clock) => clock,
+        };
+        if !clock.record_move(color, elapsed) {
+            self.set_outcome(Outcome::win(color.opposite(), Termination::Timeout));
+            return Ok(());
+        }
+        let remaining = clock.remaining(color);
+        self.play(mv)?;
+        let node = &mut self.last_nodes()[0];
+        append_comment(&mut node.comment, format!("[%clk {}]", clock::format_clk(remaining)));
+        Ok(())
+    }
+
+    // Ends the game on time if the side to move has run out after thinking
+    // for `elapsed`, and returns whether it did.
+    pub fn check_flag(&mut self, elapsed: Duration) -> bool {
+        let color = self.board.side_to_move();
+        let finished = self.is_finished();
+        let flagged = match &mut self.clock {
+            // Recording the move is what marks the flag as fallen.
+            Some(clock) if !finished && clock.is_flagged(color, elapsed) => {
+                clock.record_move(color, elapsed);
+                true
+            }
+            _ => false,
+        };
+        if flagged {
+            self.set_outcome(Outcome::win(color.opposite(), Termination::Timeout));
+        }
+        flagged
+    }
+
+    // Takes back the last move of the main line, keeping it so redo can
+    // play it again with its comments and variations. The game goes back to
+    // being unfinished: a Termination tag saying it was lost on time or
+    // adjudicated is removed, and a fallen flag is reset, though the clock
+    // that ran out stays at zero until set_remaining. Returns the move, or
+    // None at the start.
+    pub fn undo(&mut self) -> Option<Move> {
+        if self.moves.is_empty() {
+            return None;
+        }
+        if self.is_finished() {
+            self.tags.retain(|(name, value)| {
+                name != "Termination" || !(value.eq_ignore_ascii_case("time forfeit") || value.eq_ignore_ascii_case("adjudication"))
+            });
+            if let Some(clock) = &mut self.clock {
+                clock.reset_flag();
+            }
+        }
+        let node = self.last_nodes().remove(0);
+        self.moves.pop();
+        self.san.pop();
+        self.history.pop();
+        self.board = self.position_at(self.moves.len()).unwrap();
+        self.set_result("*");
+        self.draw_offer = None;
+        let mv = node.mv;
+        self.redo.push(node);
+        Some(mv)
+    }
+
+    // Plays the last undone move again.
+    pub fn redo(&mut self) -> Option<Move> {
+        let node = self.redo.pop()?;
+        let mv = node.mv;
+        self.push_node(node);
+        Some(mv)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.moves.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // The number of moves in the main line.
+    pub fn ply(&self) -> usize {
+        self.moves.len()
+    }
+
+    // The position after `ply` moves of the main line, so 0 gives the
+    // starting position. None if the game isn't that long.
+    pub fn position_at(&self, ply: usize) -> Option<Board> {
+        if ply > self.moves.len() {
+            return None;
+        }
+        let mut board = self.start.clone();
+        for mv in &self.moves[..ply] {
+            board.make_move_unchecked(*mv);
+        }
+        Some(board)
+    }
+
+    // Reads one game, with its comments, NAGs and variations.
+    pub fn from_pgn(pgn: &str) -> Result<Game, PgnError> {
+        let tokens = tokenize(pgn)?;
+
+        let tags: Vec<(String, String)> = tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Tag(name, value) => Some((name.clone(), value.clone())),
+                _ => None,
+            })
+            .collect();
+        let fen = tags.iter().find(|(name, _)| name == "FEN").map_or(STARTING_FEN, |(_, value)| value.as_str());
+        let start = Board::new_from_fen(String::from(fen)).map_err(PgnError::BadFen)?;
+
+        let mut parser = TreeParser { tokens, pos: 0, comment: None, result: None };
+        let nodes = parser.parse_line(&start, 0, false)?;
+
+        let mut game = Game::new(start);
+        game.tags = tags;
+        game.comment = parser.comment;
+        // Without a result token, fall back on the Result tag.
+        game.result = parser
+            .result
+            .or_else(|| game.tag("Result").map(String::from))
+            .unwrap_or_else(|| String::from("*"));
+        let mut main_line = nodes.first();
+        while let Some(node) = main_line {
+            game.board.make_move_unchecked(node.mv);
+            game.history.push(game.board.repetition_id());
+            game.moves.push(node.mv);
+            game.san.push(node.san.clone());
+            main_line = node.children.first();
+        }
+        game.nodes = nodes;
+        Ok(game)
+    }
+
+    // Reads an unfinished game to carry on playing it. A TimeControl tag
+    // starts the clock, with each player's time taken from the last [%clk]
+    // comment on their moves.
+    pub fn resume(pgn: &str) -> Result<Game, PgnError> {
+        let mut game = Game::from_pgn(pgn)?;
+        if game.is_adjourned() {
+            game.tags.retain(|(name, _)| name != "Termination");
+        }
+        let control = match game.tag("TimeControl").and_then(|tc| tc.parse::<TimeControl>().ok()) {
+            None => return Ok(game),
+            Some(control) => control,
+        };
+        let mut clock = Clock::new(control);
+        let mut node = game.nodes.first();
+        let mut color = game.start.side_to_move();
+        while let Some(n) = node {
+            // Playing the moves in no time moves each clock to the right stage.
+            clock.record_move(color, Duration::from_secs(0));
+            if let Some(time) = n.clock() {
+                clock.set_remaining(color, time);
+            }
+            color = color.opposite();
+            node = n.children.first();
+        }
+        game.clock = Some(clock);
+        Ok(game)
+    }
+
+    // The first moves of the game as a tree: the main line's first move
+    // followed by any alternatives to it.
+    pub fn nodes(&self) -> &[GameNode] {
+        &self.nodes
+    }
+
+    // A comment before the first move.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    // Sets a tag, replacing any earlier value. Setting "Result" also sets
+    // the game's result, leaving outcome to work out how it ended.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        if name == "Result" {
+            self.result = String::from(value);
+            self.termination = None;
+        }
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = String::from(value),
+            None => self.tags.push((String::from(name), String::from(value))),
+        }
+    }
+
+    pub fn set_event(&mut self, event: &str) {
+        self.set_tag("Event", event);
+    }
+
+    pub fn set_site(&mut self, site: &str) {
+        self.set_tag("Site", site);
+    }
+
+    // In the PGN "YYYY.MM.DD" form, with "??" for unknown parts.
+    pub fn set_date(&mut self, date: &str) {
+        self.set_tag("Date", date);
+    }
+
+    pub fn set_round(&mut self, round: &str) {
+        self.set_tag("Round", round);
+    }
+
+    pub fn set_white(&mut self, name: &str) {
+        self.set_tag("White", name);
+    }
+
+    pub fn set_black(&mut self, name: &str) {
+        self.set_tag("Black", name);
+    }
+
+    pub fn set_result(&mut self, result: &str) {
+        self.set_tag("Result", result);
+    }
+
+    // The position before the first move.
+    pub fn start_board(&self) -> &Board {
+        &self.start
+    }
+
+    // The position after the last move.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    // The moves in SAN, written the standard way whatever the file used.
+    pub fn san_moves(&self) -> &[String] {
+        &self.san
+    }
+
+    // "1-0", "0-1", "1/2-1/2" or "*" for a game that is unfinished or whose
+    // result isn't known.
+    pub fn result(&self) -> &str {
+        &self.result
+    }
+
+    // Writes the game out as PGN, with its comments, NAGs and variations.
+    // The Seven Tag Roster comes first, with "?" for anything unknown, then
+    // any other tags. Games that don't start from the usual position get
+    // SetUp and FEN tags.
+    pub fn to_pgn(&self) -> String {
+        self.to_pgn_with(&PgnFormat::default())
+    }
+
+    // Like to_pgn, with the movetext laid out as `format` says.
+    pub fn to_pgn_with(&self, format: &PgnFormat) -> String {
+        let mut pgn = String::new();
+        for name in SEVEN_TAG_ROSTER.iter() {
+            let value = match *name {
+                "Result" => self.result.as_str(),
+                "Date" => self.tag(name).unwrap_or("????.??.??"),
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+        }
+        let standard_start = self.start.to_fen() == STARTING_FEN;
+        if !standard_start {
+            pgn.push_str("[SetUp \"1\"]\n");
+            pgn.push_str(&format!("[FEN \"{}\"]\n", self.start.to_fen()));
+        }
+        for (name, value) in &self.tags {
+            let written = SEVEN_TAG_ROSTER.contains(&name.as_str()) || name == "SetUp" || name == "FEN";
+            if !written {
+                pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+            }
+        }
+        pgn.push('\n');
+
+        let mut writer = MovetextWriter::new(format);
+        if let Some(comment) = &self.comment {
+            writer.comment(comment);
+        }
+        self.write_nodes(&mut writer, &self.nodes, 0, true);
+        writer.word(&self.result);
+        pgn.push_str(&writer.finish());
+        pgn
+    }
+
+    // Move number and side to move for a ply counted from the start.
+    fn move_number(&self, ply: usize) -> (u32, PieceColor) {
+        let ply = ply + if self.start.side_to_move() == PieceColor::Black { 1 } else { 0 };
+        let color = if ply.is_multiple_of(2) { PieceColor::White } else { PieceColor::Black };
+        (self.start.fullmove_number() + (ply / 2) as u32, color)
+    }
+
+    // Returns whether a comment was written after the move.
+    fn write_move(&self, writer: &mut MovetextWriter, node: &GameNode, ply: usize, show_number: bool) -> bool {
+        let commented = match &node.starting_comment {
+            Some(comment) => writer.comment(comment),
+            None => false,
+        };
+        let (number, color) = self.move_number(ply);
+        if color == PieceColor::White {
+            writer.move_number(&format!("{}.", number));
+        } else if show_number || commented {
+            writer.word(&format!("{}...", number));
+        }
+        writer.word(&move_in_notation(node, writer.format.notation));
+        for nag in &node.nags {
+            writer.word(&format!("${}", nag));
+        }
+        match &node.comment {
+            Some(comment) => writer.comment(comment),
+            None => false,
+        }
+    }
+
+    // Writes a main line move, its alternatives in parentheses and then the
+    // rest of the line. Black moves get a number when something came between
+    // them and White's move.
+    fn write_nodes(&self, writer: &mut MovetextWriter, nodes: &[GameNode], ply: usize, show_number: bool) {
+        let main = match nodes.first() {
+            None => return,
+            Some(node) => node,
+        };
+        let commented = self.write_move(writer, main, ply, show_number);
+        for variation in &nodes[1..] {
+            writer.start_variation();
+            let commented = self.write_move(writer, variation, ply, true);
+            self.write_nodes(writer, &variation.children, ply + 1, commented);
+            writer.end_variation();
+        }
+        let interrupted = nodes.len() > 1 || commented;
+        self.write_nodes(writer, &main.children, ply + 1, interrupted);
+    }
+}
+
+// How moves are written. Games in any of them can be read back in.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum MoveNotation {
+    San,
+    // The from and to squares in full, e.g. Ng1-f3 or e4xd5.
+    LongAlgebraic,
+    // As UCI engines write them, e.g. g1f3 or e7e8q.
+    Uci,
+    // SAN with figurines for the pieces, e.g. \u{2658}f3.
+    Figurine,
+}
+
+impl FromStr for MoveNotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MoveNotation, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "san" => Ok(MoveNotation::San),
+            "long-algebraic" | "lan" => Ok(MoveNotation::LongAlgebraic),
+            "uci" => Ok(MoveNotation::Uci),
+            "figurine" => Ok(MoveNotation::Figurine),
+            _ => Err(format!("Unknown notation \"{}\".", s)),
+        }
+    }
+}
+
+// The move in the given notation. Everything the other notations need
+// beyond the move itself, like the piece letter or whether it captures
+// or checks, can be read off its SAN.
+fn move_in_notation(node: &GameNode, notation: MoveNotation) -> String {
+    let san = node.san.as_str();
+    match notation {
+        MoveNotation::San => String::from(san),
+        MoveNotation::Uci => node.mv.to_uci(),
+        MoveNotation::Figurine => san
+            .chars()
+            .map(|c| match c {
+                'K' => '\u{2654}',
+                'Q' => '\u{2655}',
+                'R' => '\u{2656}',
+                'B' => '\u{2657}',
+                'N' => '\u{2658}',
+                _ => c,
+            })
+            .collect(),
+        MoveNotation::LongAlgebraic => {
+            if san.starts_with("O-O") {
+                return String::from(san);
+            }
+            let mut text: String = san.chars().take_while(|c| "KQRBN".contains(*c)).collect();
+            let capture = if san.contains('x') { 'x' } else { '-' };
+            text.push_str(&format!("{}{}{}", node.mv.from, capture, node.mv.to));
+            if let Some(i) = san.find('=') {
+                text.push_str(&san[i..i + 2]);
+            }
+            if san.ends_with('+') || san.ends_with('#') {
+                text.push_str(&san[san.len() - 1..]);
+            }
+            text
+        }
+    }
+}
+
+// Reads a move written in any of the MoveNotations. SAN is tried first;
+// anything it doesn't take is read as long algebraic or UCI, which both give
+// the from and to squares. The SAN error is returned if neither works.
+fn move_from_notation(board: &Board, text: &str) -> Result<Move, SanError> {
+    let san: String = text
+        .chars()
+        .map(|c| match c {
+            '\u{2654}' | '\u{265a}' => 'K',
+            '\u{2655}' | '\u{265b}' => 'Q',
+            '\u{2656}' | '\u{265c}' => 'R',
+            '\u{2657}' | '\u{265d}' => 'B',
+            '\u{2658}' | '\u{265e}' => 'N',
+            _ => c,
+        })
+        .collect();
+    let error = match board.move_from_san(&san) {
+        Ok(mv) => return Ok(mv),
+        Err(error) => error,
+    };
+    let mut squares = san.trim_end_matches(&['+', '#'][..]);
+    let piece = squares.chars().next().filter(|c| "KQRBN".contains(*c));
+    if piece.is_some() {
+        squares = &squares[1..];
+    }
+    let uci: String = squares.chars().filter(|c| !"-x=".contains(*c)).collect();
+    let mv = match Move::from_uci(&uci.to_ascii_lowercase()) {
+        Ok(mv) if board.legal_moves().contains(&mv) => mv,
+        _ => return Err(error),
+    };
+    // A piece letter, if given, has to match the piece that moves.
+    let moving = board.piece_at(mv.from).map(|(ptype, _)| ptype);
+    let expected = match piece {
+        None => moving,
+        Some('K') => Some(PieceType::King),
+        Some('Q') => Some(PieceType::Queen),
+        Some('R') => Some(PieceType::Rook),
+        Some('B') => Some(PieceType::Bishop),
+        Some(_) => Some(PieceType::Knight),
+    };
+    if moving != expected {
+        return Err(error);
+    }
+    Ok(mv)
+}
+
+// How the movetext is laid out.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum MoveLayout {
+    // As many moves as fit on a line of at most this many characters.
+    Wrapped(usize),
+    // Each full move starts a new line. Lines aren't wrapped, so long
+    // comments and variations stay on the line of their move.
+    OnePerLine,
+}
+
+// Options for writing PGN. The default is the usual export format: SAN,
+// with lines wrapped at 80 characters and comments and variations inline.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct PgnFormat {
+    pub layout: MoveLayout,
+    // Put each comment on a line of its own.
+    pub comments_on_own_line: bool,
+    // Start each variation on a new line, indented two spaces per level of
+    // nesting, and carry on after it on a new line.
+    pub indent_variations: bool,
+    // Keep commands like [%eval 0.25] and [%clk 0:03:00] in comments. Some
+    // readers choke on them; without them a comment left empty is dropped.
+    pub annotations: bool,
+    pub notation: MoveNotation,
+}
+
+impl Default for PgnFormat {
+    fn default() -> PgnFormat {
+        PgnFormat {
+            layout: MoveLayout::Wrapped(LINE_LENGTH),
+            comments_on_own_line: false,
+            indent_variations: false,
+            annotations: true,
+            notation: MoveNotation::San,
+        }
+    }
+}
+
+// Strips the [%command ...] annotations out of a comment.
+fn strip_annotations(comment: &str) -> String {
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        text.push_str(&rest[..start]);
+        rest = match rest[start..].find(']') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    text.push_str(rest);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Builds movetext a word at a time, breaking lines as the format asks.
+struct MovetextWriter<'a> {
+    format: &'a PgnFormat,
+    text: String,
+    line: String,
+    depth: usize,
+    // A comment on its own line ends it, but a closing parenthesis may
+    // still follow on the same line.
+    break_pending: bool,
+}
+
+impl<'a> MovetextWriter<'a> {
+    fn new(format: &'a PgnFormat) -> MovetextWriter<'a> {
+        MovetextWriter { format, text: String::new(), line: String::new(), depth: 0, break_pending: false }
+    }
+
+    fn indent(&self) -> usize {
+        if self.format.indent_variations { 2 * self.depth } else { 0 }
+    }
+
+    fn word(&mut self, word: &str) {
+        if self.break_pending && word != ")" {
+            self.new_line();
+        }
+        let indent = self.indent();
+        if self.line.is_empty() {
+            self.line.push_str(&" ".repeat(indent));
+            self.line.push_str(word);
+            return;
+        }
+        // Parentheses hug the variation inside them.
+        if !self.line.ends_with('(') && word != ")" {
+            if let MoveLayout::Wrapped(length) = self.format.layout {
+                if self.line.len() + 1 + word.len() > length {
+                    self.new_line();
+                    self.line.push_str(&" ".repeat(indent));
+                    self.line.push_str(word);
+                    return;
+                }
+            }
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+    }
+
+    // Ends the current line, unless nothing has been written on it yet.
+    fn new_line(&mut self) {
+        self.break_pending = false;
+        if !self.line.trim().is_empty() {
+            self.text.push_str(&self.line);
+            self.text.push('\n');
+        }
+        self.line.clear();
+    }
+
+    // White's move numbers, where one move per line breaks.
+    fn move_number(&mut self, number: &str) {
+        if self.format.layout == MoveLayout::OnePerLine && !self.line.ends_with('(') {
+            self.new_line();
+        }
+        self.word(number);
+    }
+
+    // Returns false if nothing was left of the comment to write.
+    fn comment(&mut self, comment: &str) -> bool {
+        let text = if self.format.annotations { comment.to_string() } else { strip_annotations(comment) };
+        if text.is_empty() && !self.format.annotations {
+            return false;
+        }
+        if self.format.comments_on_own_line {
+            self.new_line();
+        }
+        for word in format!("{{{}}}", text).split_whitespace() {
+            self.word(word);
+        }
+        self.break_pending = self.format.comments_on_own_line;
+        true
+    }
+
+    fn start_variation(&mut self) {
+        if self.format.indent_variations {
+            self.new_line();
+        }
+        self.depth += 1;
+        self.word("(");
+    }
+
+    fn end_variation(&mut self) {
+        self.word(")");
+        self.depth -= 1;
+        if self.format.indent_variations {
+            self.new_line();
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.new_line();
+        self.text
+    }
+}
+
+// Reads the games in a PGN file one at a time, so only one game is held in
+// memory however large the file is. A game that can't be read is returned as
+// an error and reading carries on with the next one; use
+// `filter_map(Result::ok)` to skip them. Reading stops after an I/O error.
+pub struct PgnReader<R: Read> {
+    reader: BufReader<R>,
+    // The first line of the next game, read while looking for the end of the
+    // previous one.
+    pending: Option<String>,
+    done: bool,
+}
+
+impl<R: Read> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader { reader: BufReader::new(reader), pending: None, done: false }
+    }
+
+    // Invalid UTF-8 is replaced rather than failing the whole file.
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut bytes = Vec::new();
+        if self.reader.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    // Collects the lines of the next game. A game ends where a tag pair
+    // starts after some movetext, or at the end of the input.
+    fn next_game_text(&mut self) -> io::Result<Option<String>> {
+        let mut text = String::new();
+        let mut has_movetext = false;
+        let mut in_comment = false;
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => match self.read_line()? {
+                    None => break,
+                    Some(line) => line,
+                },
+            };
+            let trimmed = line.trim_start_matches('\u{feff}').trim_start();
+            if !in_comment && trimmed.starts_with('[') {
+                if has_movetext {
+                    self.pending = Some(line);
+                    break;
+                }
+            } else if !in_comment && !trimmed.is_empty() && !trimmed.starts_with('%') {
+                has_movetext = true;
+            }
+            for c in trimmed.chars() {
+                match c {
+                    '}' if in_comment => in_comment = false,
+                    '{' if !in_comment => in_comment = true,
+                    ';' if !in_comment => break,
+                    _ => {}
+                }
+            }
+            text.push_str(&line);
+        }
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(text))
+    }
+}
+
+impl<R: Read> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Result<Game, PgnError>> {
+        if self.done {
+            return None;
+        }
+        match self.next_game_text() {
+            Err(e) => {
+                self.done = true;
+                Some(Err(PgnError::Io(e.kind())))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Ok(Some(text)) => Some(Game::from_pgn(&text)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LICHESS: &str = r#"[Event "Rated Blitz game"]
+[Site "https://lichess.org/abcdefgh"]
+[White "alice"]
+[Black "bob \"the rook\""]
+[Result "1-0"]
+
+1. e4 { [%clk 0:03:00] } 1... e5 2. Bc4 Nc6 3. Qh5 $1 Nf6?? (3... g6 4. Qf3 Nf6) 4. Qxf7# 1-0
+"#;
+
+    #[test]
+    fn test_lichess_game() {
+        let game = Game::from_pgn(LICHESS).expect("");
+        assert_eq!(game.tag("White"), Some("alice"));
+        assert_eq!(game.tag("Black"), Some("bob \"the rook\""));
+        assert_eq!(game.tag("Date"), None);
+        assert_eq!(game.tags().len(), 5);
+        assert_eq!(game.result(), "1-0");
+        assert_eq!(game.san_moves(), &["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+        assert_eq!(game.moves()[6], Move::from_uci("h5f7").unwrap());
+        assert!(game.board().is_checkmate());
+        assert_eq!(game.start_board().to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn test_game_tree() {
+        let game = Game::from_pgn(LICHESS).expect("");
+        let e4 = &game.nodes()[0];
+        assert_eq!(game.nodes().len(), 1);
+        assert_eq!(e4.comment.as_deref(), Some("[%clk 0:03:00]"));
+        let qh5 = &e4.children[0].children[0].children[0].children[0];
+        assert_eq!(qh5.san, "Qh5");
+        assert_eq!(qh5.nags, vec![1]);
+        assert_eq!(qh5.children.len(), 2);
+        assert_eq!(qh5.children[0].san, "Nf6");
+        assert_eq!(qh5.children[0].nags, vec![4]);
+        let g6 = &qh5.children[1];
+        assert_eq!(g6.san, "g6");
+        assert_eq!(g6.children[0].san, "Qf3");
+        assert_eq!(g6.children[0].children[0].san, "Nf6");
+
+        let pgn = game.to_pgn();
+        assert!(pgn.ends_with(
+            "1. e4 {[%clk 0:03:00]} 1... e5 2. Bc4 Nc6 3. Qh5 $1 Nf6 $4 (3... g6 4. Qf3 Nf6)\n4. Qxf7# 1-0\n"
+        ));
+        let read = Game::from_pgn(&pgn).expect("");
+        assert_eq!(read.nodes(), game.nodes());
+    }
+
+    #[test]
+    fn test_nested_variations_and_comments() {
+        let pgn = "{Opening practice} 1. e4 (1. d4 d5 (1... Nf6 2. c4) 2. c4) ({Or} 1. c4 $14) 1... e5 ; main line\n2. Nf3 *";
+        let game = Game::from_pgn(pgn).expect("");
+        assert_eq!(game.comment(), Some("Opening practice"));
+        assert_eq!(game.san_moves(), &["e4", "e5", "Nf3"]);
+        let first = game.nodes();
+        assert_eq!(first.iter().map(|n| n.san.as_str()).collect::<Vec<_>>(), vec!["e4", "d4", "c4"]);
+        assert_eq!(first[1].children.len(), 2);
+        assert_eq!(first[1].children[1].san, "Nf6");
+        assert_eq!(first[2].starting_comment.as_deref(), Some("Or"));
+        assert_eq!(first[2].nags, vec![14]);
+        assert_eq!(first[0].children[0].comment.as_deref(), Some("main line"));
+
+        let written = game.to_pgn();
+        assert!(written.ends_with(
+            "{Opening practice} 1. e4 (1. d4 d5 (1... Nf6 2. c4) 2. c4) ({Or} 1. c4 $14) 1...\ne5 {main line} 2. Nf3 *\n"
+        ));
+        assert_eq!(Game::from_pgn(&written).expect("").nodes(), game.nodes());
+
+        // A variation needs a move to replace.
+        assert_eq!(Game::from_pgn("(1. d4) 1. e4").unwrap_err(), PgnError::UnbalancedVariation);
+        match Game::from_pgn("1. e4 (1. e5)") {
+            Err(PgnError::BadMove { ply: 0, .. }) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fen_tag_and_compact_movetext() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n\n1...Kd7 2.e4 Ke6 3.O-O *";
+        match Game::from_pgn(pgn) {
+            Err(PgnError::BadMove { ply: 3, .. }) => {}
+            other => panic!("{:?}", other),
+        }
+        let pgn = "[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 1\"]\n\n1...Kd7 2.e4 Ke6\n% a comment for some program\n3.Kd2 ; a rest of line comment\n*";
+        let game = Game::from_pgn(pgn).expect("");
+        assert_eq!(game.san_moves(), &["Kd7", "e4", "Ke6", "Kd2"]);
+        assert_eq!(game.result(), "*");
+    }
+
+    #[test]
+    fn test_result_from_tag() {
+        let game = Game::from_pgn("[Result \"1/2-1/2\"]\n1. e4 e5").expect("");
+        assert_eq!(game.result(), "1/2-1/2");
+        assert_eq!(Game::from_pgn("1. e4 e5").expect("").result(), "*");
+    }
+
+    #[test]
+    fn test_to_pgn() {
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        game.set_white("alice");
+        game.set_black("bob \"the rook\"");
+        game.set_date("2024.01.31");
+        game.set_tag("TimeControl", "180+2");
+        for uci in ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"].iter() {
+            game.play(Move::from_uci(uci).unwrap()).expect("");
+        }
+        assert!(game.play(Move::from_uci("e8f7").unwrap()).is_err());
+        game.set_result("1-0");
+        let pgn = game.to_pgn();
+        assert_eq!(
+            pgn,
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"2024.01.31\"]\n[Round \"?\"]\n[White \"alice\"]\n\
+             [Black \"bob \\\"the rook\\\"\"]\n[Result \"1-0\"]\n[TimeControl \"180+2\"]\n\n\
+             1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"
+        );
+        let read = Game::from_pgn(&pgn).expect("");
+        assert_eq!(read.moves(), game.moves());
+        assert_eq!(read.tag("Black"), Some("bob \"the rook\""));
+    }
+
+    #[test]
+    fn test_to_pgn_wrapping_and_setup() {
+        // Kings walking back and forth, long enough to need several lines,
+        // until the fivefold repetition ends the game as a draw.
+        let fen = "4k3/p7/8/8/8/8/P7/4K3 b - - 0 30";
+        let mut game = Game::new(Board::new_from_fen(String::from(fen)).expect(""));
+        let walk = ["e8d8", "e1d1", "d8c8", "d1c1", "c8d8", "c1d1", "d8e8", "d1e1"];
+        for uci in walk.iter().cycle() {
+            if game.is_finished() {
+                break;
+            }
+            game.play(Move::from_uci(uci).unwrap()).expect("");
+        }
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/p7/8/8/8/8/P7/4K3 b - - 0 30\"]\n"));
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.starts_with("30... Kd8 31. Kd1 Kc8 32. Kc1 Kd8"));
+        assert!(movetext.lines().count() > 1);
+        assert!(movetext.lines().all(|l| l.len() <= LINE_LENGTH));
+        assert!(movetext.ends_with(" 1/2-1/2\n"));
+        assert_eq!(Game::from_pgn(&pgn).expect("").moves(), game.moves());
+    }
+
+    #[test]
+    fn test_to_pgn_with_format() {
+        let pgn = "1. e4 { [%eval 0.3] [%clk 0:03:00] } e5 (1... c5 { Sicilian [%clk 0:02:58] }) 2. Nf3 *";
+        let game = Game::from_pgn(pgn).expect("");
+        let movetext = |format: &PgnFormat| game.to_pgn_with(format).split("\n\n").nth(1).unwrap().to_string();
+
+        let default = PgnFormat::default();
+        assert_eq!(
+            movetext(&default),
+            "1. e4 {[%eval 0.3] [%clk 0:03:00]} 1... e5 (1... c5 {Sicilian [%clk 0:02:58]})\n2. Nf3 *\n"
+        );
+        assert_eq!(game.to_pgn(), game.to_pgn_with(&default));
+
+        let plain = PgnFormat { annotations: false, ..default };
+        // With the annotations gone, e4's comment is empty and left out.
+        assert_eq!(movetext(&plain), "1. e4 e5 (1... c5 {Sicilian}) 2. Nf3 *\n");
+
+        let one_per_line = PgnFormat { layout: MoveLayout::OnePerLine, annotations: false, ..default };
+        assert_eq!(movetext(&one_per_line), "1. e4 e5 (1... c5 {Sicilian})\n2. Nf3 *\n");
+
+        let indented = PgnFormat { indent_variations: true, comments_on_own_line: true, annotations: false, ..default };
+        assert_eq!(movetext(&indented), "1. e4 e5\n  (1... c5\n  {Sicilian})\n2. Nf3 *\n");
+
+        let narrow = PgnFormat { layout: MoveLayout::Wrapped(20), ..plain };
+        assert_eq!(movetext(&narrow), "1. e4 e5 (1... c5\n{Sicilian}) 2. Nf3 *\n");
+        for format in [default, plain, one_per_line, indented, narrow].iter() {
+            let read = Game::from_pgn(&game.to_pgn_with(format)).expect("");
+            assert_eq!(read.nodes()[0].children.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        assert_eq!(game.undo(), None);
+        // Castling through the bishop and knight is refused, not played.
+        assert_eq!(game.play(Move::from_uci("e1g1").unwrap()), Err(MoveError::Illegal));
+        assert_eq!(game.ply(), 0);
+        let line = ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"];
+        for uci in line.iter() {
+            game.play(Move::from_uci(uci).unwrap()).expect("");
+        }
+        assert_eq!(game.result(), "1-0");
+        assert_eq!(game.ply(), 7);
+        assert_eq!(game.position_at(0).unwrap().to_fen(), STARTING_FEN);
+        assert_eq!(game.position_at(1).unwrap().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(game.position_at(8).is_none());
+
+        assert_eq!(game.undo(), Some(Move::from_uci("h5f7").unwrap()));
+        assert_eq!(game.undo(), Some(Move::from_uci("g8f6").unwrap()));
+        assert_eq!(game.result(), "*");
+        assert_eq!(game.board().to_fen(), game.position_at(5).unwrap().to_fen());
+        assert!(game.can_redo());
+        assert_eq!(game.redo(), Some(Move::from_uci("g8f6").unwrap()));
+        assert_eq!(game.redo(), Some(Move::from_uci("h5f7").unwrap()));
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.result(), "1-0");
+        assert!(game.board().is_checkmate());
+
+        // Playing something else after an undo drops the redo list.
+        game.undo();
+        game.undo();
+        game.play(Move::from_uci("g7g6").unwrap()).expect("");
+        assert!(!game.can_redo());
+        assert_eq!(game.san_moves(), &["e4", "e5", "Bc4", "Nc6", "Qh5", "g6"]);
+        assert!(game.play(Move::from_uci("h5f7").unwrap()).is_err());
+        assert_eq!(game.ply(), 6);
+    }
+
+    #[test]
+    fn test_repetition() {
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        assert_eq!(game.repetition_count(), 1);
+        for uci in shuffle.iter() {
+            game.play(Move::from_uci(uci).unwrap()).expect("");
+        }
+        assert_eq!(game.repetition_count(), 2);
+        for uci in shuffle.iter() {
+            game.play(Move::from_uci(uci).unwrap()).expect("");
+        }
+        assert!(game.is_threefold_repetition());
+        assert_eq!(game.result(), "*");
+        game.undo();
+        assert!(!game.is_threefold_repetition());
+        game.redo();
+        for _ in 0..2 {
+            for uci in shuffle.iter() {
+                game.play(Move::from_uci(uci).unwrap()).expect("");
+            }
+        }
+        assert!(game.is_fivefold_repetition());
+        assert_eq!(game.result(), "1/2-1/2");
+
+        // 1. e4 sets an en passant square black can't use, so the position
+        // after it repeats once the knights have gone out and back.
+        let pgn = "1. e4 Nf6 2. Nf3 Ng8 3. Ng1 Nf6 4. Nf3 Ng8 5. Ng1 *";
+        assert!(Game::from_pgn(pgn).expect("").is_threefold_repetition());
+    }
+
+    #[test]
+    fn test_seventy_five_move_rule() {
+        let mut game = Game::new(Board::new_from_fen(String::from("4k3/8/8/8/8/8/8/R3K3 w - - 149 100")).expect(""));
+        assert!(game.board().is_fifty_move_draw());
+        game.play(Move::from_uci("a1a2").unwrap()).expect("");
+        assert_eq!(game.result(), "1/2-1/2");
+    }
+
+    #[test]
+    fn test_insufficient_material_draws() {
+        let mut game = Game::new(Board::new_from_fen(String::from("4k3/8/8/8/8/8/3r4/3RK3 b - - 0 1")).expect(""));
+        game.play(Move::from_uci("d2d1").unwrap()).expect("");
+        assert_eq!(game.result(), "*");
+        game.play(Move::from_uci("e1d1").unwrap()).expect("");
+        assert_eq!(game.result(), "1/2-1/2");
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut game = Game::from_pgn(LICHESS).expect("");
+        assert_eq!(game.outcome(), Some(Outcome::WhiteWins(Termination::Checkmate)));
+        game.undo();
+        assert_eq!(game.outcome(), None);
+        game.set_outcome(Outcome::BlackWins(Termination::Timeout));
+        assert_eq!(game.result(), "0-1");
+        let read = Game::from_pgn(&game.to_pgn()).expect("");
+        assert_eq!(read.outcome(), Some(Outcome::BlackWins(Termination::Timeout)));
+
+        let outcome = |pgn: &str| Game::from_pgn(pgn).expect("").outcome();
+        assert_eq!(outcome("1. e4 e5 1-0"), Some(Outcome::WhiteWins(Termination::Resignation)));
+        assert_eq!(outcome("1. e4 e5 1/2-1/2"), Some(Outcome::Draw(Termination::Agreement)));
+        assert_eq!(outcome("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 1/2-1/2"), Some(Outcome::Draw(Termination::Repetition)));
+        assert_eq!(outcome("1. e4 e5 *"), None);
+
+        let mut game = Game::new(Board::new_from_fen(String::from("k7/8/1Q6/8/8/8/8/6K1 w - - 0 1")).expect(""));
+        game.play(Move::from_uci("b6c7").unwrap()).expect("");
+        assert_eq!(game.outcome(), Some(Outcome::Draw(Termination::Stalemate)));
+        game.undo();
+        game.play(Move::from_uci("b6b5").unwrap()).expect("");
+        assert_eq!(game.outcome(), None);
+    }
+
+    #[test]
+    fn test_move_notations() {
+        let game = Game::from_pgn("1. e4 d5 2. exd5 Qxd5 3. Nc3 Qe5+ 4. Be2 Bg4 5. d4 Bxe2 6. Ngxe2 Nc6 7. O-O *").expect("");
+        let movetext = |notation: MoveNotation| {
+            let format = PgnFormat { notation, layout: MoveLayout::Wrapped(200), ..PgnFormat::default() };
+            game.to_pgn_with(&format).split("\n\n").nth(1).unwrap().trim_end().to_string()
+        };
+        assert_eq!(
+            movetext(MoveNotation::LongAlgebraic),
+            "1. e2-e4 d7-d5 2. e4xd5 Qd8xd5 3. Nb1-c3 Qd5-e5+ 4. Bf1-e2 Bc8-g4 5. d2-d4 Bg4xe2 6. Ng1xe2 Nb8-c6 7. O-O *"
+        );
+        assert_eq!(movetext(MoveNotation::Uci), "1. e2e4 d7d5 2. e4d5 d8d5 3. b1c3 d5e5 4. f1e2 c8g4 5. d2d4 g4e2 6. g1e2 b8c6 7. e1g1 *");
+        assert!(movetext(MoveNotation::Figurine).starts_with("1. e4 d5 2. exd5 \u{2655}xd5 3. \u{2658}c3 \u{2655}e5+"));
+
+        let promotion = Game::from_pgn("[FEN \"1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. axb8=Q+ *").expect("");
+        let format = PgnFormat { notation: MoveNotation::LongAlgebraic, ..PgnFormat::default() };
+        assert!(promotion.to_pgn_with(&format).contains("1. a7xb8=Q+ *"));
+        assert_eq!("lan".parse(), Ok(MoveNotation::LongAlgebraic));
+        assert!("figurines".parse::<MoveNotation>().is_err());
+
+        // Every notation reads back in as the same moves.
+        for notation in [MoveNotation::LongAlgebraic, MoveNotation::Uci, MoveNotation::Figurine].iter() {
+            let format = PgnFormat { notation: *notation, ..PgnFormat::default() };
+            assert_eq!(Game::from_pgn(&game.to_pgn_with(&format)).expect("").moves(), game.moves());
+            assert_eq!(Game::from_pgn(&promotion.to_pgn_with(&format)).expect("").moves(), promotion.moves());
+        }
+        // The piece letter in long algebraic has to be right.
+        assert!(Game::from_pgn("1. Bg1-f3 *").is_err());
+    }
+
+    #[test]
+    fn test_draw_offers_and_resignation() {
+        let play = |game: &mut Game, uci: &str| game.play(Move::from_uci(uci).unwrap()).expect("");
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        assert!(game.accept_draw().is_err());
+        // White offers along with a move, and black moving declines it.
+        play(&mut game, "e2e4");
+        game.offer_draw(PieceColor::White).expect("");
+        assert_eq!(game.draw_offer(), Some(PieceColor::White));
+        play(&mut game, "e7e5");
+        assert_eq!(game.draw_offer(), None);
+        assert!(game.accept_draw().is_err());
+
+        game.offer_draw(PieceColor::White).expect("");
+        game.accept_draw().expect("");
+        assert_eq!(game.outcome(), Some(Outcome::Draw(Termination::Agreement)));
+        assert!(game.offer_draw(PieceColor::Black).is_err());
+        assert!(game.resign(PieceColor::Black).is_err());
+        assert_eq!(game.play(Move::from_uci("g1f3").unwrap()), Err(MoveError::GameOver));
+
+        game.undo();
+        game.resign(PieceColor::White).expect("");
+        assert_eq!(game.result(), "0-1");
+        assert_eq!(game.outcome(), Some(Outcome::BlackWins(Termination::Resignation)));
+        // No moves after resigning, and the movetext ends with the last one
+        // played before it.
+        assert_eq!(game.play(Move::from_uci("e7e5").unwrap()), Err(MoveError::GameOver));
+        assert!(game.to_pgn().ends_with("1. e4 0-1\n"));
+    }
+
+    #[test]
+    fn test_adjudication() {
+        // Games that reach move 3 are adjudicated as drawn.
+        fn move_limit(game: &Game) -> Option<Outcome> {
+            if game.board().fullmove_number() >= 3 {
+                Some(Outcome::Draw(Termination::Agreement))
+            } else {
+                None
+            }
+        }
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        game.set_adjudicator(Some(move_limit));
+        for uci in ["g1f3", "g8f6", "f3g1"].iter() {
+            game.play(Move::from_uci(uci).unwrap()).expect("");
+            assert_eq!(game.result(), "*");
+        }
+        game.play(Move::from_uci("f6g8").unwrap()).expect("");
+        assert_eq!(game.outcome(), Some(Outcome::Draw(Termination::Agreement)));
+        assert_eq!(game.tag("Termination"), Some("adjudication"));
+
+        assert_eq!(game.play(Move::from_uci("g1f3").unwrap()), Err(MoveError::GameOver));
+        assert_eq!(game.ply(), 4);
+
+        game.adjudicate(Outcome::WhiteWins(Termination::Timeout));
+        assert_eq!(game.result(), "1-0");
+        assert_eq!(game.outcome(), Some(Outcome::WhiteWins(Termination::Timeout)));
+
+        // Taking a move back reopens the game and drops the tag.
+        game.undo();
+        assert_eq!(game.tag("Termination"), None);
+        game.set_result("1-0");
+        assert_eq!(game.outcome(), Some(Outcome::WhiteWins(Termination::Resignation)));
+    }
+
+    #[test]
+    fn test_clock() {
+        let secs = Duration::from_secs;
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        game.set_clock(Clock::new(TimeControl::fischer(secs(180), secs(2))));
+        game.play_timed(Move::from_uci("e2e4").unwrap(), secs(5)).expect("");
+        assert!(game.play_timed(Move::from_uci("e2e4").unwrap(), secs(5)).is_err());
+        game.play_timed(Move::from_uci("e7e5").unwrap(), secs(1)).expect("");
+        assert_eq!(game.nodes()[0].comment.as_deref(), Some("[%clk 0:02:57]"));
+        assert_eq!(game.nodes()[0].children[0].clock(), Some(secs(181)));
+
+        assert!(!game.check_flag(secs(177)));
+        assert!(game.check_flag(secs(178)));
+        assert_eq!(game.outcome(), Some(Outcome::BlackWins(Termination::Timeout)));
+        // The game is over, so the move is refused without touching White's
+        // clock.
+        let remaining = game.clock().unwrap().remaining(PieceColor::White);
+        assert_eq!(game.play_timed(Move::from_uci("g1f3").unwrap(), secs(1)), Err(MoveError::GameOver));
+        assert_eq!(game.clock().unwrap().remaining(PieceColor::White), remaining);
+        assert_eq!(game.ply(), 2);
+
+        // Undoing reopens the game with White's flag no longer down, so
+        // moves can be played again once White has time.
+        game.undo();
+        assert_eq!(game.tag("Termination"), None);
+        assert_eq!(game.clock().unwrap().flagged(), None);
+        game.play_timed(Move::from_uci("e7e5").unwrap(), secs(1)).expect("");
+        game.set_clock({
+            let mut clock = game.clock().unwrap().clone();
+            clock.set_remaining(PieceColor::White, secs(60));
+            clock
+        });
+        game.play_timed(Move::from_uci("g1f3").unwrap(), secs(1)).expect("");
+        assert_eq!(game.outcome(), None);
+        game.set_result("1-0");
+        assert_eq!(game.outcome(), Some(Outcome::WhiteWins(Termination::Resignation)));
+
+        // A move made after the flag fell doesn't count.
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        game.set_clock(Clock::new(TimeControl::sudden_death(secs(60))));
+        game.play_timed(Move::from_uci("e2e4").unwrap(), secs(61)).expect("");
+        assert!(game.moves().is_empty());
+        assert_eq!(game.outcome(), Some(Outcome::BlackWins(Termination::Timeout)));
+        assert_eq!(game.clock().unwrap().flagged(), Some(PieceColor::White));
+        assert_eq!(game.play(Move::from_uci("e2e4").unwrap()), Err(MoveError::GameOver));
+
+        let read = Game::from_pgn(LICHESS).expect("");
+        assert_eq!(read.nodes()[0].clock(), Some(secs(180)));
+        assert_eq!(read.nodes()[0].children[0].clock(), None);
+    }
+
+    #[test]
+    fn test_adjourn_and_resume() {
+        let secs = Duration::from_secs;
+        let mut game = Game::new(Board::new_from_fen(String::from(STARTING_FEN)).expect(""));
+        game.set_tag("TimeControl", "2/60:30");
+        game.set_clock(Clock::new("2/60:30".parse().unwrap()));
+        for (uci, elapsed) in [("e2e4", 10), ("e7e5", 20), ("g1f3", 5)].iter() {
+            game.play_timed(Move::from_uci(uci).unwrap(), secs(*elapsed)).expect("");
+        }
+        assert!(!game.is_finished());
+        game.adjourn().expect("");
+        assert!(game.is_adjourned());
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("[Termination \"unterminated\"]"));
+        assert!(pgn.ends_with(" *\n"));
+
+        let mut resumed = Game::resume(&pgn).expect("");
+        assert!(!resumed.is_adjourned());
+        assert_eq!(resumed.tag("Termination"), None);
+        assert_eq!(resumed.moves(), game.moves());
+        assert_eq!(resumed.clock(), game.clock());
+        // White has moved into the second stage, black hasn't yet.
+        let clock = resumed.clock().unwrap();
+        assert_eq!(clock.remaining(PieceColor::White), secs(75));
+        assert_eq!(clock.remaining(PieceColor::Black), secs(40));
+        resumed.play_timed(Move::from_uci("b8c6").unwrap(), secs(1)).expect("");
+        assert_eq!(resumed.clock().unwrap().remaining(PieceColor::Black), secs(69));
+
+        // Without a usable time control there is no clock.
+        assert_eq!(Game::resume("[TimeControl \"-\"]\n\n1. e4 *").expect("").clock(), None);
+        let mut finished = Game::from_pgn(LICHESS).expect("");
+        assert!(finished.is_finished());
+        assert!(finished.adjourn().is_err());
+    }
+
+    #[test]
+    fn test_undo_keeps_annotations() {
+        let mut game = Game::from_pgn("1. e4 {best by test} (1. d4) 1... e5 *").expect("");
+        game.undo();
+        game.undo();
+        assert_eq!(game.ply(), 0);
+        game.redo();
+        game.redo();
+        assert_eq!(game.to_pgn(), Game::from_pgn("1. e4 {best by test} (1. d4) 1... e5 *").expect("").to_pgn());
+    }
+
+    #[test]
+    fn test_reader() {
+        let file = "[Event \"one\"]\n\n1. e4 e5 1-0\n\n\
+                    [Event \"two\"]\n\n1. e4 e5 2. Ke3 0-1\n\n\
+                    [Event \"three\"]\n[Result \"*\"]\n\n1. d4 {a comment\n[that starts a line]} d5\n*\n\
+                    [Event \"four\"]\n1. c4";
+        let games: Vec<Result<Game, PgnError>> = PgnReader::new(file.as_bytes()).collect();
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].as_ref().unwrap().tag("Event"), Some("one"));
+        assert!(matches!(games[1], Err(PgnError::BadMove { ply: 2, .. })));
+        let three = games[2].as_ref().unwrap();
+        assert_eq!(three.san_moves(), &["d4", "d5"]);
+        assert_eq!(three.nodes()[0].comment.as_deref(), Some("a comment\n[that starts a line]"));
+        assert_eq!(games[3].as_ref().unwrap().san_moves(), &["c4"]);
+
+        let good = PgnReader::new(file.as_bytes()).filter_map(Result::ok).count();
+        assert_eq!(good, 3);
+        assert_eq!(PgnReader::new("\n\n".as_bytes()).count(), 0);
+        assert!(PgnReader::new("\u{feff}[Event \"bom\"]\n1. e4 *".as_bytes()).next().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Game::from_pgn("[White alice]\n1. e4").unwrap_err(), PgnError::BadTag);
+        assert_eq!(Game::from_pgn("[White \"alice]\n1. e4").unwrap_err(), PgnError::Unterminated);
+        assert_eq!(Game::from_pgn("1. e4 { open").unwrap_err(), PgnError::Unterminated);
+        assert_eq!(Game::from_pgn("1. e4 (1. d4 e5").unwrap_err(), PgnError::UnbalancedVariation);
+        assert_eq!(Game::from_pgn("1. e4 ) e5").unwrap_err(), PgnError::UnbalancedVariation);
+        assert_eq!(Game::from_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]").unwrap_err(), PgnError::BadFen(FenError::MissingKing));
+        assert_eq!(
+            Game::from_pgn("1. e4 e5 2. Ke3").unwrap_err(),
+            PgnError::BadMove { ply: 2, san: String::from("Ke3"), error: SanError::Illegal }
+        );
+    }
+}
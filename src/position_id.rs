@@ -0,0 +1,96 @@
+use std::fmt;
+
+// A 64 bit identifier for a position, made by Board::position_id.
+// Two boards with the same pieces, side to move, castling rights and en passant
+// square always get the same id. The move clocks are not part of it.
+// The hash is fixed (FNV-1a over a canonical byte layout) so ids can be stored
+// in databases and URLs and still mean the same thing in later versions.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash, PartialOrd, Ord)]
+pub struct PositionId(pub u64);
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// Length of a short code: 64 bits at 6 bits per character.
+const SHORT_CODE_LEN: usize = 11;
+
+impl PositionId {
+    // Encodes the id as 11 characters of URL safe base64 (the big endian
+    // bytes of the id, without padding), e.g. for use in a link.
+    pub fn to_short_code(self) -> String {
+        // Base64 works on whole 6 bit groups, so the 64 bits are padded
+        // with two zero bits at the end.
+        let bits = (self.0 as u128) << 2;
+        (0..SHORT_CODE_LEN)
+            .map(|i| ALPHABET[((bits >> (6 * (SHORT_CODE_LEN - 1 - i))) & 63) as usize] as char)
+            .collect()
+    }
+
+    pub fn from_short_code(code: &str) -> Result<PositionId, String> {
+        if code.len() != SHORT_CODE_LEN {
+            return Err(String::from("Invalid position short code length."));
+        }
+        let mut bits: u128 = 0;
+        for c in code.bytes() {
+            let value = match ALPHABET.iter().position(|&a| a == c) {
+                None => return Err(String::from("Invalid character in position short code.")),
+                Some(v) => v as u128,
+            };
+            bits = (bits << 6) | value;
+        }
+        if bits & 3 != 0 {
+            return Err(String::from("Invalid position short code."));
+        }
+        Ok(PositionId((bits >> 2) as u64))
+    }
+}
+
+impl fmt::Display for PositionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_short_code())
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// Small FNV-1a hasher used to build position ids. This is deliberately not
+// std's DefaultHasher, whose output is allowed to change between releases.
+pub(crate) struct Fnv(u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Fnv {
+        Fnv(FNV_OFFSET)
+    }
+
+    pub(crate) fn write_u8(&mut self, byte: u8) {
+        self.0 ^= byte as u64;
+        self.0 = self.0.wrapping_mul(FNV_PRIME);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_code_round_trip() {
+        for &id in [0, 1, 0xdead_beef, u64::MAX, 0x0123_4567_89ab_cdef].iter() {
+            let code = PositionId(id).to_short_code();
+            assert_eq!(code.len(), 11);
+            assert_eq!(PositionId::from_short_code(&code), Ok(PositionId(id)));
+        }
+        // Same as base64url of the bytes 01 23 45 67 89 ab cd ef.
+        assert_eq!(PositionId(0x0123_4567_89ab_cdef).to_short_code(), "ASNFZ4mrze8");
+    }
+
+    #[test]
+    fn test_bad_short_codes() {
+        assert!(PositionId::from_short_code("ASNFZ4mrze").is_err());
+        assert!(PositionId::from_short_code("ASNFZ4mrze+").is_err());
+        assert!(PositionId::from_short_code("ASNFZ4mrze9").is_err());
+    }
+}
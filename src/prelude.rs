@@ -0,0 +1,12 @@
+// The types most code using this crate needs, so they can all be brought in
+// with `use chess_rust::prelude::*;`. Only add things here that are meant to
+// stay stable; helpers that are still changing should be imported from their
+// own modules.
+pub use crate::bitboard::Bitboard;
+pub use crate::castling::{CastlingRights, CastlingSide};
+pub use crate::eval::Score;
+pub use crate::game::{Board, FenError, PieceColor, PieceType, UndoInfo};
+pub use crate::moves::{Move, MoveError};
+pub use crate::position_id::PositionId;
+pub use crate::san::SanError;
+pub use crate::square::{Direction, Square, SquareColor};
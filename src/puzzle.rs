@@ -0,0 +1,162 @@
+use crate::game::Board;
+use crate::moves::{Move, MoveError};
+use crate::square::Square;
+
+// Runs one tactics puzzle: the player is shown a position, tries moves, and
+// the opponent's answers from the solution are played automatically.
+
+// What happened when the player tried a move.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Attempt {
+    // The move was right and the opponent answered with this move.
+    Correct(Move),
+    // The move was right and finished the puzzle.
+    Solved,
+    // A legal move, but not the solution. The board is left as it was so
+    // the player can try again.
+    Wrong,
+    // Not a legal move in the position.
+    Illegal,
+}
+
+#[derive(Debug, Clone)]
+pub struct PuzzleSession {
+    board: Board,
+
+    // The full solution, alternating the player's moves and the opponent's
+    // replies, starting with the player's first move.
+    solution: Vec<Move>,
+
+    // How many moves of the solution have been played so far.
+    ply: usize,
+
+    mistakes: u32,
+    hints: u32,
+}
+
+impl PuzzleSession {
+    // Starts a puzzle from a position with the player to move. Fails if the
+    // solution isn't a legal line from that position.
+    pub fn new(board: Board, solution: Vec<Move>) -> Result<PuzzleSession, MoveError> {
+        let mut check = board.clone();
+        for mv in &solution {
+            check.make_move(*mv)?;
+        }
+        Ok(PuzzleSession { board, solution, ply: 0, mistakes: 0, hints: 0 })
+    }
+
+    // The current position, to show to the player.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn attempt(&mut self, mv: Move) -> Attempt {
+        if self.is_complete() {
+            return Attempt::Solved;
+        }
+        if !self.board.legal_moves().contains(&mv) {
+            return Attempt::Illegal;
+        }
+        let expected = self.solution[self.ply];
+        let is_last = self.ply + 1 == self.solution.len();
+        if mv != expected {
+            // Any mate is as good as the one in the solution.
+            let mut after = self.board.clone();
+            after.make_move_unchecked(mv);
+            if is_last && after.is_checkmate() {
+                self.board = after;
+                self.ply = self.solution.len();
+                return Attempt::Solved;
+            }
+            self.mistakes += 1;
+            return Attempt::Wrong;
+        }
+        self.board.make_move_unchecked(mv);
+        self.ply += 1;
+        if self.ply == self.solution.len() {
+            return Attempt::Solved;
+        }
+        let reply = self.solution[self.ply];
+        self.board.make_move_unchecked(reply);
+        self.ply += 1;
+        if self.ply == self.solution.len() {
+            // A solution shouldn't end on the opponent's move, but if it does
+            // there's nothing left for the player to find.
+            return Attempt::Solved;
+        }
+        Attempt::Correct(reply)
+    }
+
+    // Shows which piece the player should move next, and counts it as a hint.
+    pub fn hint(&mut self) -> Option<Square> {
+        if self.is_complete() {
+            return None;
+        }
+        self.hints += 1;
+        Some(self.solution[self.ply].from)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.ply >= self.solution.len()
+    }
+
+    // True if the puzzle was finished without any wrong moves or hints.
+    pub fn is_clean_solve(&self) -> bool {
+        self.is_complete() && self.mistakes == 0 && self.hints == 0
+    }
+
+    pub fn mistakes(&self) -> u32 {
+        self.mistakes
+    }
+
+    pub fn hints(&self) -> u32 {
+        self.hints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move::new(from.parse().unwrap(), to.parse().unwrap())
+    }
+
+    #[test]
+    fn test_solving() {
+        // A made up line: the knight attacks the queen, black ignores it and
+        // white takes it.
+        let b = board("r3k3/8/8/3q4/8/8/4N3/4K3 w - - 0 1");
+        let mut puzzle = PuzzleSession::new(b, vec![mv("e2", "c3"), mv("e8", "e7"), mv("c3", "d5")]).expect("");
+        assert_eq!(puzzle.attempt(mv("e2", "g3")), Attempt::Wrong);
+        assert_eq!(puzzle.attempt(mv("e2", "e4")), Attempt::Illegal);
+        assert_eq!(puzzle.hint(), Some("e2".parse().unwrap()));
+        assert_eq!(puzzle.attempt(mv("e2", "c3")), Attempt::Correct(mv("e8", "e7")));
+        assert!(!puzzle.is_complete());
+        assert_eq!(puzzle.attempt(mv("c3", "d5")), Attempt::Solved);
+        assert!(puzzle.is_complete());
+        assert_eq!(puzzle.mistakes(), 1);
+        assert_eq!(puzzle.hints(), 1);
+        assert!(!puzzle.is_clean_solve());
+        assert_eq!(puzzle.hint(), None);
+    }
+
+    #[test]
+    fn test_alternative_mate_accepted() {
+        // Both Ra8 and Rb8 mate; the solution only lists one.
+        let b = board("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1");
+        let mut puzzle = PuzzleSession::new(b, vec![mv("a1", "a8")]).expect("");
+        assert_eq!(puzzle.attempt(mv("b1", "b8")), Attempt::Solved);
+        assert!(puzzle.is_clean_solve());
+    }
+
+    #[test]
+    fn test_bad_solution() {
+        let b = board("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1");
+        assert!(PuzzleSession::new(b, vec![mv("a1", "a8"), mv("a8", "a7")]).is_err());
+    }
+}
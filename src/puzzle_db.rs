@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::json::{self, Json, JsonParser};
+
+// Puzzle collections in the CSV layout of the lichess puzzle dump or as JSON
+// lines, and an SM-2 spaced repetition scheduler for deciding which puzzles
+// to show again.
+//
+// The lichess columns are:
+// PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+// The FEN is the position before the opponent's move that sets up the puzzle,
+// so the first of the moves is played by the opponent. Moves are in UCI form
+// and are kept as strings here. In JSON lines each puzzle is an object on
+// its own line with the column names as keys, and the moves, themes and
+// opening tags as arrays of strings.
+
+const HEADER: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags";
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PuzzleRecord {
+    pub id: String,
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub rating: u32,
+    pub rating_deviation: u32,
+    pub popularity: i32,
+    pub plays: u32,
+    pub themes: Vec<String>,
+    pub game_url: String,
+    pub opening_tags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum PuzzleDbError {
+    Io(io::Error),
+    // A line that couldn't be read as a puzzle or review record. Lines are
+    // counted from 1.
+    BadRecord(usize),
+}
+
+impl fmt::Display for PuzzleDbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleDbError::Io(e) => write!(f, "{}", e),
+            PuzzleDbError::BadRecord(line) => write!(f, "Invalid record on line {}.", line),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleDbError {}
+
+impl From<io::Error> for PuzzleDbError {
+    fn from(e: io::Error) -> PuzzleDbError {
+        PuzzleDbError::Io(e)
+    }
+}
+
+fn split_words(s: &str) -> Vec<String> {
+    s.split_whitespace().map(String::from).collect()
+}
+
+impl PuzzleRecord {
+    fn from_csv_line(line: &str) -> Option<PuzzleRecord> {
+        let fields: Vec<&str> = line.split(',').collect();
+        // Older dumps stop after the game url.
+        if fields.len() != 9 && fields.len() != 10 {
+            return None;
+        }
+        let moves = split_words(fields[2]);
+        if fields[0].is_empty() || fields[1].is_empty() || moves.is_empty() {
+            return None;
+        }
+        Some(PuzzleRecord {
+            id: String::from(fields[0]),
+            fen: String::from(fields[1]),
+            moves,
+            rating: fields[3].parse().ok()?,
+            rating_deviation: fields[4].parse().ok()?,
+            popularity: fields[5].parse().ok()?,
+            plays: fields[6].parse().ok()?,
+            themes: split_words(fields[7]),
+            game_url: String::from(fields[8]),
+            opening_tags: fields.get(9).map(|s| split_words(s)).unwrap_or_default(),
+        })
+    }
+
+    fn to_csv_line(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.id,
+            self.fen,
+            self.moves.join(" "),
+            self.rating,
+            self.rating_deviation,
+            self.popularity,
+            self.plays,
+            self.themes.join(" "),
+            self.game_url,
+            self.opening_tags.join(" ")
+        )
+    }
+
+    fn from_json_line(line: &str) -> Option<PuzzleRecord> {
+        let json = JsonParser::parse(line).ok()?;
+        let text = |key: &str| json.get(key).and_then(Json::as_str).map(String::from);
+        let words = |key: &str| -> Option<Vec<String>> {
+            match json.get(key) {
+                Some(Json::Array(items)) => items.iter().map(|item| item.as_str().map(String::from)).collect(),
+                _ => None,
+            }
+        };
+        let record = PuzzleRecord {
+            id: text("PuzzleId")?,
+            fen: text("FEN")?,
+            moves: words("Moves")?,
+            rating: json_integer(&json, "Rating")?,
+            rating_deviation: json_integer(&json, "RatingDeviation")?,
+            popularity: json_integer(&json, "Popularity")?,
+            plays: json_integer(&json, "NbPlays")?,
+            themes: words("Themes")?,
+            game_url: text("GameUrl")?,
+            opening_tags: if json.get("OpeningTags").is_some() { words("OpeningTags")? } else { Vec::new() },
+        };
+        if record.id.is_empty() || record.fen.is_empty() || record.moves.is_empty() {
+            return None;
+        }
+        Some(record)
+    }
+
+    fn to_json_line(&self) -> String {
+        let list = |words: &[String]| {
+            let quoted: Vec<String> = words.iter().map(|w| json::quote(w)).collect();
+            format!("[{}]", quoted.join(","))
+        };
+        format!(
+            "{{\"PuzzleId\":{},\"FEN\":{},\"Moves\":{},\"Rating\":{},\"RatingDeviation\":{},\"Popularity\":{},\
+             \"NbPlays\":{},\"Themes\":{},\"GameUrl\":{},\"OpeningTags\":{}}}",
+            json::quote(&self.id),
+            json::quote(&self.fen),
+            list(&self.moves),
+            self.rating,
+            self.rating_deviation,
+            self.popularity,
+            self.plays,
+            list(&self.themes),
+            json::quote(&self.game_url),
+            list(&self.opening_tags)
+        )
+    }
+
+    pub fn has_theme(&self, theme: &str) -> bool {
+        self.themes.iter().any(|t| t == theme)
+    }
+}
+
+// A whole number field of a JSON record that fits in T.
+fn json_integer<T: TryFrom<i64>>(json: &Json, key: &str) -> Option<T> {
+    let n = json.get(key).and_then(Json::as_f64)?;
+    if n.fract() != 0.0 {
+        return None;
+    }
+    T::try_from(n as i64).ok()
+}
+
+// Reads the puzzles of a collection one at a time, so the full lichess dump
+// doesn't have to fit in memory. Lines starting with "{" are read as JSON and
+// anything else as CSV, so either layout works. The CSV header line is
+// optional and blank lines are skipped.
+pub struct PuzzleReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> PuzzleReader<R> {
+    pub fn new(reader: R) -> PuzzleReader<R> {
+        PuzzleReader { lines: reader.lines(), line: 0 }
+    }
+}
+
+impl<R: BufRead> Iterator for PuzzleReader<R> {
+    type Item = Result<PuzzleRecord, PuzzleDbError>;
+
+    fn next(&mut self) -> Option<Result<PuzzleRecord, PuzzleDbError>> {
+        loop {
+            let line = match self.lines.next()? {
+                Err(e) => return Some(Err(PuzzleDbError::Io(e))),
+                Ok(line) => line,
+            };
+            self.line += 1;
+            let line = line.trim_end();
+            if line.is_empty() || (self.line == 1 && line.starts_with("PuzzleId,")) {
+                continue;
+            }
+            let record = if line.starts_with('{') {
+                PuzzleRecord::from_json_line(line)
+            } else {
+                PuzzleRecord::from_csv_line(line)
+            };
+            return Some(record.ok_or(PuzzleDbError::BadRecord(self.line)));
+        }
+    }
+}
+
+// Reads a whole puzzle collection, stopping at the first bad record.
+pub fn read_puzzles<R: BufRead>(reader: R) -> Result<Vec<PuzzleRecord>, PuzzleDbError> {
+    PuzzleReader::new(reader).collect()
+}
+
+pub fn write_puzzles<W: Write>(mut writer: W, puzzles: &[PuzzleRecord]) -> io::Result<()> {
+    writeln!(writer, "{}", HEADER)?;
+    for puzzle in puzzles {
+        writeln!(writer, "{}", puzzle.to_csv_line())?;
+    }
+    Ok(())
+}
+
+// Writes puzzles as JSON lines, one object per puzzle.
+pub fn write_puzzles_jsonl<W: Write>(mut writer: W, puzzles: &[PuzzleRecord]) -> io::Result<()> {
+    for puzzle in puzzles {
+        writeln!(writer, "{}", puzzle.to_json_line())?;
+    }
+    Ok(())
+}
+
+// The SM-2 state of one puzzle. Days are plain day numbers chosen by the
+// caller, e.g. days since the epoch.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Card {
+    pub due: u32,
+    pub interval: u32,
+    pub repetitions: u32,
+    pub ease: f64,
+}
+
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+impl Card {
+    // A puzzle that hasn't been reviewed yet, due straight away.
+    pub fn new(today: u32) -> Card {
+        Card { due: today, interval: 0, repetitions: 0, ease: INITIAL_EASE }
+    }
+
+    // Updates the card after a review. Quality is the usual SM-2 grade from
+    // 0 (no idea) to 5 (perfect); anything below 3 counts as a failure and
+    // starts the card over.
+    pub fn review(&mut self, quality: u8, today: u32) {
+        let q = quality.min(5) as f64;
+        if quality < 3 {
+            self.repetitions = 0;
+            self.interval = 1;
+        } else {
+            self.interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u32,
+            };
+            self.repetitions += 1;
+        }
+        self.ease = (self.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(MIN_EASE);
+        self.due = today + self.interval;
+    }
+
+    pub fn is_due(&self, today: u32) -> bool {
+        self.due <= today
+    }
+}
+
+fn parse_card(line: &str) -> Option<(String, Card)> {
+    let fields: Vec<&str> = line.split(',').collect();
+    if fields.len() != 5 || fields[0].is_empty() {
+        return None;
+    }
+    let card = Card {
+        due: fields[1].parse().ok()?,
+        interval: fields[2].parse().ok()?,
+        repetitions: fields[3].parse().ok()?,
+        ease: fields[4].parse().ok()?,
+    };
+    Some((String::from(fields[0]), card))
+}
+
+// Review state for a set of puzzles, keyed by puzzle id.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Scheduler {
+    cards: HashMap<String, Card>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    // Starts tracking a puzzle. Does nothing if it is already tracked.
+    pub fn add(&mut self, id: &str, today: u32) {
+        self.cards.entry(String::from(id)).or_insert_with(|| Card::new(today));
+    }
+
+    pub fn card(&self, id: &str) -> Option<&Card> {
+        self.cards.get(id)
+    }
+
+    // Records a review, adding the puzzle first if it wasn't tracked.
+    pub fn review(&mut self, id: &str, quality: u8, today: u32) {
+        self.cards.entry(String::from(id)).or_insert_with(|| Card::new(today)).review(quality, today);
+    }
+
+    // The puzzles due today, most overdue first.
+    pub fn due(&self, today: u32) -> Vec<&str> {
+        let mut due: Vec<(&String, &Card)> = self.cards.iter().filter(|(_, c)| c.is_due(today)).collect();
+        due.sort_by(|a, b| a.1.due.cmp(&b.1.due).then(a.0.cmp(b.0)));
+        due.into_iter().map(|(id, _)| id.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    // Reads the state saved by write, one "id,due,interval,repetitions,ease"
+    // line per puzzle.
+    pub fn read<R: BufRead>(reader: R) -> Result<Scheduler, PuzzleDbError> {
+        let mut scheduler = Scheduler::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            match parse_card(line) {
+                None => return Err(PuzzleDbError::BadRecord(i + 1)),
+                Some((id, card)) => {
+                    scheduler.cards.insert(id, card);
+                }
+            }
+        }
+        Ok(scheduler)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut ids: Vec<&String> = self.cards.keys().collect();
+        ids.sort();
+        for id in ids {
+            let c = &self.cards[id];
+            writeln!(writer, "{},{},{},{},{}", id, c.due, c.interval, c.repetitions, c.ease)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00008,r6k/pp2r2p/4Rp1Q/3p4/8/1N1P2R1/PqP2bPP/7K b - - 0 24,f2g3 e6e7 b2b1 b3c1 b1c1 h6c1,1913,75,94,6230,crushing hangingPiece long middlegame,https://lichess.org/787zsVup/black#48,
+0000D,5rk1/1p3ppp/pq3b2/8/8/1P1Q1N2/P4PPP/3R2K1 w - - 2 27,d3d6 f8d8 d6d8 f6d8,1452,74,96,26441,advantage endgame short,https://lichess.org/F8M8OS71#53,Sicilian_Defense Sicilian_Defense_Alapin_Variation
+";
+
+    #[test]
+    fn test_read_write() {
+        let puzzles = read_puzzles(DUMP.as_bytes()).expect("");
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[0].id, "00008");
+        assert_eq!(puzzles[0].moves.len(), 6);
+        assert_eq!(puzzles[0].moves[0], "f2g3");
+        assert_eq!(puzzles[0].rating, 1913);
+        assert!(puzzles[0].has_theme("hangingPiece"));
+        assert!(puzzles[0].opening_tags.is_empty());
+        assert_eq!(puzzles[1].opening_tags.len(), 2);
+
+        let mut out = Vec::new();
+        write_puzzles(&mut out, &puzzles).expect("");
+        assert_eq!(String::from_utf8(out).unwrap(), DUMP);
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let puzzles = read_puzzles(DUMP.as_bytes()).expect("");
+        let mut out = Vec::new();
+        write_puzzles_jsonl(&mut out, &puzzles).expect("");
+        let jsonl = String::from_utf8(out).unwrap();
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(jsonl.starts_with(r#"{"PuzzleId":"00008","FEN":"r6k/pp2r2p/4Rp1Q/3p4/8/1N1P2R1/PqP2bPP/7K b - - 0 24","Moves":["f2g3","#));
+        assert_eq!(read_puzzles(jsonl.as_bytes()).expect(""), puzzles);
+
+        // Records are read one at a time, and a bad one doesn't hide those
+        // before it.
+        let mixed = format!("{}{{\"PuzzleId\":\"x\"}}\n", jsonl);
+        let mut reader = PuzzleReader::new(mixed.as_bytes());
+        assert_eq!(reader.next().unwrap().expect("").id, "00008");
+        assert_eq!(reader.next().unwrap().expect("").id, "0000D");
+        match reader.next() {
+            Some(Err(PuzzleDbError::BadRecord(3))) => {}
+            other => panic!("{:?}", other),
+        }
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_bad_record() {
+        let bad = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags\n\
+                   0000D,8/8/8/8/8/8/8/8 w - - 0 1,e2e4,abc,74,96,26441,short,url,\n";
+        match read_puzzles(bad.as_bytes()) {
+            Err(PuzzleDbError::BadRecord(2)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sm2() {
+        let mut card = Card::new(10);
+        assert!(card.is_due(10));
+        card.review(5, 10);
+        assert_eq!((card.due, card.interval), (11, 1));
+        card.review(4, 11);
+        assert_eq!((card.due, card.interval), (17, 6));
+        card.review(4, 17);
+        // 6 days times the ease, which went up by 0.1 for the perfect answer.
+        assert_eq!(card.interval, 16);
+        assert!(!card.is_due(30));
+        card.review(1, 33);
+        assert_eq!((card.due, card.repetitions), (34, 0));
+        assert!(card.ease < 2.6);
+        for _ in 0..10 {
+            card.review(0, 34);
+        }
+        assert_eq!(card.ease, MIN_EASE);
+    }
+
+    #[test]
+    fn test_scheduler() {
+        let mut scheduler = Scheduler::new();
+        scheduler.add("a", 0);
+        scheduler.add("b", 0);
+        scheduler.review("c", 5, 0);
+        assert_eq!(scheduler.due(0), vec!["a", "b"]);
+        scheduler.review("a", 5, 0);
+        assert_eq!(scheduler.due(1), vec!["b", "a", "c"]);
+
+        let mut out = Vec::new();
+        scheduler.write(&mut out).expect("");
+        let read = Scheduler::read(out.as_slice()).expect("");
+        assert_eq!(read, scheduler);
+    }
+}
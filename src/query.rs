@@ -0,0 +1,356 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::pgn::Game;
+
+// A small language for picking games out by their tags, e.g.
+//   elo>2500 AND eco startswith "B9" AND result=1-0
+// Comparisons are a tag name, an operator and a value, and can be combined
+// with AND, OR, NOT and parentheses. The operators are = != < <= > >=,
+// contains and startswith. Values are compared as numbers when both sides
+// are numbers and otherwise as text, ignoring case, so dates in the PGN
+// "YYYY.MM.DD" form sort correctly. Two names are special: "result" is the
+// game's result and "elo" is the lower of the two players' ratings, so
+// elo>2500 means both players are over 2500. A comparison with a tag the
+// game doesn't have is false.
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum QueryError {
+    UnexpectedEnd,
+    // A token where something else was needed, e.g. a value where an
+    // operator should be.
+    Unexpected(String),
+    UnterminatedString,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::UnexpectedEnd => write!(f, "Query ends too early."),
+            QueryError::Unexpected(token) => write!(f, "Unexpected \"{}\" in query.", token),
+            QueryError::UnterminatedString => write!(f, "Query has a string without its closing quote."),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Token {
+    Word(String),
+    // A quoted value, which is never taken as a keyword.
+    Text(String),
+    Symbol(&'static str),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Symbol("(")),
+            ')' => tokens.push(Token::Symbol(")")),
+            '=' => tokens.push(Token::Symbol("=")),
+            '!' | '<' | '>' => {
+                let with_equals = chars.peek() == Some(&'=');
+                if with_equals {
+                    chars.next();
+                }
+                tokens.push(Token::Symbol(match (c, with_equals) {
+                    ('!', true) => "!=",
+                    ('<', false) => "<",
+                    ('<', true) => "<=",
+                    ('>', false) => ">",
+                    ('>', true) => ">=",
+                    _ => return Err(QueryError::Unexpected(String::from("!"))),
+                }));
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(QueryError::UnterminatedString),
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            _ => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()=!<>\"".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+    StartsWith,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Field {
+    Result,
+    Elo,
+    Tag(String),
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        field: Field,
+        operator: Operator,
+        // Lowercased, and parsed ahead of time when it is a number.
+        value: String,
+        number: Option<i64>,
+    },
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    match token {
+        Some(Token::Word(word)) => word.eq_ignore_ascii_case(keyword),
+        _ => false,
+    }
+}
+
+// Recursive descent over the tokens. OR binds loosest, then AND, then NOT.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, QueryError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(QueryError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.and()?;
+        while is_keyword(self.peek(), "or") {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.unary()?;
+        while is_keyword(self.peek(), "and") {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, QueryError> {
+        if is_keyword(self.peek(), "not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::Symbol("(")) {
+            self.pos += 1;
+            let expr = self.or()?;
+            return match self.next()? {
+                Token::Symbol(")") => Ok(expr),
+                token => Err(unexpected(token)),
+            };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, QueryError> {
+        let field = match self.next()? {
+            Token::Word(name) if name.eq_ignore_ascii_case("result") => Field::Result,
+            Token::Word(name) if name.eq_ignore_ascii_case("elo") => Field::Elo,
+            Token::Word(name) => Field::Tag(name),
+            token => return Err(unexpected(token)),
+        };
+        let operator = match self.next()? {
+            Token::Symbol("=") => Operator::Equal,
+            Token::Symbol("!=") => Operator::NotEqual,
+            Token::Symbol("<") => Operator::Less,
+            Token::Symbol("<=") => Operator::LessOrEqual,
+            Token::Symbol(">") => Operator::Greater,
+            Token::Symbol(">=") => Operator::GreaterOrEqual,
+            Token::Word(word) if word.eq_ignore_ascii_case("contains") => Operator::Contains,
+            Token::Word(word) if word.eq_ignore_ascii_case("startswith") => Operator::StartsWith,
+            token => return Err(unexpected(token)),
+        };
+        let value = match self.next()? {
+            Token::Word(value) | Token::Text(value) => value.to_lowercase(),
+            token => return Err(unexpected(token)),
+        };
+        let number = value.parse().ok();
+        Ok(Expr::Compare { field, operator, value, number })
+    }
+}
+
+fn unexpected(token: Token) -> QueryError {
+    QueryError::Unexpected(match token {
+        Token::Word(word) => word,
+        Token::Text(text) => format!("\"{}\"", text),
+        Token::Symbol(symbol) => String::from(symbol),
+    })
+}
+
+fn tag<'a>(game: &'a Game, name: &str) -> Option<&'a str> {
+    game.tags().iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+fn field_value(game: &Game, field: &Field) -> Option<String> {
+    match field {
+        Field::Result => Some(String::from(game.result())),
+        Field::Elo => {
+            let elo = |name: &str| tag(game, name).and_then(|v| v.parse::<i64>().ok());
+            Some(elo("WhiteElo")?.min(elo("BlackElo")?).to_string())
+        }
+        Field::Tag(name) => tag(game, name).map(String::from),
+    }
+}
+
+impl Expr {
+    fn matches(&self, game: &Game) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(game) && b.matches(game),
+            Expr::Or(a, b) => a.matches(game) || b.matches(game),
+            Expr::Not(expr) => !expr.matches(game),
+            Expr::Compare { field, operator, value, number } => {
+                let actual = match field_value(game, field) {
+                    None => return false,
+                    Some(actual) => actual.to_lowercase(),
+                };
+                let ordering = match (actual.parse::<i64>(), number) {
+                    (Ok(actual), Some(number)) => actual.cmp(number),
+                    _ => actual.as_str().cmp(value.as_str()),
+                };
+                match operator {
+                    Operator::Equal => ordering == Ordering::Equal,
+                    Operator::NotEqual => ordering != Ordering::Equal,
+                    Operator::Less => ordering == Ordering::Less,
+                    Operator::LessOrEqual => ordering != Ordering::Greater,
+                    Operator::Greater => ordering == Ordering::Greater,
+                    Operator::GreaterOrEqual => ordering != Ordering::Less,
+                    Operator::Contains => actual.contains(value.as_str()),
+                    Operator::StartsWith => actual.starts_with(value.as_str()),
+                }
+            }
+        }
+    }
+}
+
+// A parsed query, ready to be tested against any number of games.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Query(Expr);
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, QueryError> {
+        let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Query(expr)),
+            Some(token) => Err(unexpected(token.clone())),
+        }
+    }
+
+    pub fn matches(&self, game: &Game) -> bool {
+        self.0.matches(game)
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Query, QueryError> {
+        Query::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnReader;
+
+    const GAMES: &str = r#"[Event "Candidates"]
+[Date "2024.04.05"]
+[WhiteElo "2758"]
+[BlackElo "2727"]
+[ECO "B90"]
+[Result "1-0"]
+
+1. e4 c5 1-0
+
+[Event "Club championship"]
+[Date "2023.11.20"]
+[WhiteElo "2510"]
+[BlackElo "1980"]
+[ECO "B92"]
+[Result "1-0"]
+
+1. e4 c5 1-0
+
+[Event "Candidates"]
+[Date "2024.04.06"]
+[WhiteElo "2725"]
+[BlackElo "2803"]
+[ECO "C65"]
+[Result "1/2-1/2"]
+
+1. e4 e5 1/2-1/2
+"#;
+
+    fn matching(query: &str) -> Vec<usize> {
+        let query = Query::parse(query).expect("");
+        let games = PgnReader::new(GAMES.as_bytes()).map(|g| g.expect(""));
+        games.enumerate().filter(|(_, game)| query.matches(game)).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_queries() {
+        assert_eq!(matching("elo>2500 AND eco startswith \"B9\" AND result=1-0"), vec![0]);
+        assert_eq!(matching("eco startswith b9"), vec![0, 1]);
+        assert_eq!(matching("result = 1/2-1/2 OR BlackElo < 2000"), vec![1, 2]);
+        assert_eq!(matching("NOT event contains candidates"), vec![1]);
+        assert_eq!(matching("date >= 2024.04.06 or (whiteelo>=2758 and not result=0-1)"), vec![0, 2]);
+        // A tag the games don't have matches nothing, even with !=.
+        assert_eq!(matching("Opening != Sicilian"), Vec::<usize>::new());
+        assert_eq!(matching("elo >= 2725"), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Query::parse("elo >"), Err(QueryError::UnexpectedEnd));
+        assert_eq!(Query::parse("elo 2500"), Err(QueryError::Unexpected(String::from("2500"))));
+        assert_eq!(Query::parse("(elo > 2500"), Err(QueryError::UnexpectedEnd));
+        assert_eq!(Query::parse("elo > 2500)"), Err(QueryError::Unexpected(String::from(")"))));
+        assert_eq!(Query::parse("event = \"Candidates"), Err(QueryError::UnterminatedString));
+        assert_eq!(Query::parse("elo ! 2500"), Err(QueryError::Unexpected(String::from("!"))));
+        assert!("result=1-0 and elo>2000".parse::<Query>().is_ok());
+    }
+}
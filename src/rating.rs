@@ -0,0 +1,226 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::puzzle::PuzzleSession;
+
+// Glicko-2 ratings for players and puzzles. A solved puzzle counts as a win
+// for the player and a loss for the puzzle, and a failed one the other way
+// round, the same way lichess rates its puzzles.
+
+// Converts between the Glicko scale and the internal Glicko-2 scale.
+const SCALE: f64 = 173.7178;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    // A new player or puzzle nothing is known about yet.
+    fn default() -> Rating {
+        Rating { rating: 1500.0, deviation: 350.0, volatility: 0.06 }
+    }
+}
+
+// Saved as "rating/deviation/volatility", e.g. "1500/350/0.06".
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.rating, self.deviation, self.volatility)
+    }
+}
+
+impl FromStr for Rating {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Rating, String> {
+        let fields: Vec<&str> = s.split('/').collect();
+        let parse = |field: &str| field.parse::<f64>().map_err(|_| format!("Invalid rating \"{}\".", s));
+        if fields.len() != 3 {
+            return Err(format!("Invalid rating \"{}\".", s));
+        }
+        Ok(Rating { rating: parse(fields[0])?, deviation: parse(fields[1])?, volatility: parse(fields[2])? })
+    }
+}
+
+// The system constants. tau limits how quickly the volatility can change.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Glicko2 {
+    pub tau: f64,
+    pub max_deviation: f64,
+}
+
+impl Default for Glicko2 {
+    fn default() -> Glicko2 {
+        Glicko2 { tau: 0.75, max_deviation: 350.0 }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+fn expected(mu: f64, mu_j: f64, phi_j: f64) -> f64 {
+    1.0 / (1.0 + (-g(phi_j) * (mu - mu_j)).exp())
+}
+
+impl Glicko2 {
+    // The player's new rating after one rating period. Each result is an
+    // opponent with the score against them: 1 for a win, 0.5 for a draw and
+    // 0 for a loss.
+    pub fn update(&self, player: Rating, results: &[(Rating, f64)]) -> Rating {
+        let mu = (player.rating - 1500.0) / SCALE;
+        let phi = player.deviation / SCALE;
+        let sigma = player.volatility;
+
+        if results.is_empty() {
+            // Nothing played, so only the uncertainty grows.
+            let deviation = (phi * phi + sigma * sigma).sqrt() * SCALE;
+            return Rating { deviation: deviation.min(self.max_deviation), ..player };
+        }
+
+        let mut v_inv = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in results {
+            let mu_j = (opponent.rating - 1500.0) / SCALE;
+            let phi_j = opponent.deviation / SCALE;
+            let e = expected(mu, mu_j, phi_j);
+            v_inv += g(phi_j) * g(phi_j) * e * (1.0 - e);
+            improvement += g(phi_j) * (score - e);
+        }
+        let v = 1.0 / v_inv;
+        let delta = v * improvement;
+
+        let sigma = self.new_volatility(phi, sigma, v, delta);
+        let phi_star = (phi * phi + sigma * sigma).sqrt();
+        let phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let mu = mu + phi * phi * improvement;
+        Rating {
+            rating: mu * SCALE + 1500.0,
+            deviation: (phi * SCALE).min(self.max_deviation),
+            volatility: sigma,
+        }
+    }
+
+    // Finds the new volatility with the Illinois variant of regula falsi, as
+    // in step 5 of Glickman's description of the system.
+    fn new_volatility(&self, phi: f64, sigma: f64, v: f64, delta: f64) -> f64 {
+        const EPSILON: f64 = 0.000_001;
+        let tau = self.tau;
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let d = phi * phi + v + ex;
+            ex * (delta * delta - phi * phi - v - ex) / (2.0 * d * d) - (x - a) / (tau * tau)
+        };
+
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * tau) < 0.0 {
+                k += 1.0;
+            }
+            a - k * tau
+        };
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > EPSILON {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        (lower / 2.0).exp()
+    }
+
+    // Rates a finished puzzle attempt, updating both the player's and the
+    // puzzle's ratings. A puzzle counts as solved only without mistakes or
+    // hints. Returns false and changes nothing if the player is still
+    // working on it without having gone wrong yet.
+    pub fn rate_puzzle(&self, session: &PuzzleSession, player: &mut Rating, puzzle: &mut Rating) -> bool {
+        let score = if session.is_clean_solve() {
+            1.0
+        } else if session.mistakes() > 0 || session.hints() > 0 {
+            0.0
+        } else {
+            return false;
+        };
+        let old_player = *player;
+        *player = self.update(old_player, &[(*puzzle, score)]);
+        *puzzle = self.update(*puzzle, &[(old_player, 1.0 - score)]);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Board;
+    use crate::moves::Move;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating { rating, deviation, volatility: 0.06 }
+    }
+
+    #[test]
+    fn test_glickman_example() {
+        // The worked example from Glickman's paper.
+        let system = Glicko2 { tau: 0.5, ..Glicko2::default() };
+        let results = [(rating(1400.0, 30.0), 1.0), (rating(1550.0, 100.0), 0.0), (rating(1700.0, 300.0), 0.0)];
+        let new = system.update(rating(1500.0, 200.0), &results);
+        assert!((new.rating - 1464.06).abs() < 0.01, "{}", new);
+        assert!((new.deviation - 151.52).abs() < 0.01, "{}", new);
+        assert!((new.volatility - 0.05999).abs() < 0.00001, "{}", new);
+    }
+
+    #[test]
+    fn test_no_games() {
+        let new = Glicko2::default().update(rating(1500.0, 200.0), &[]);
+        assert_eq!(new.rating, 1500.0);
+        assert!(new.deviation > 200.0);
+        assert_eq!(Glicko2::default().update(Rating::default(), &[]).deviation, 350.0);
+    }
+
+    #[test]
+    fn test_rate_puzzle() {
+        let board = Board::new_from_fen(String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).expect("");
+        let mate = Move::from_uci("a1a8").unwrap();
+        let system = Glicko2::default();
+
+        let mut session = PuzzleSession::new(board.clone(), vec![mate]).expect("");
+        let mut player = Rating::default();
+        let mut puzzle = Rating::default();
+        assert!(!system.rate_puzzle(&session, &mut player, &mut puzzle));
+        session.attempt(mate);
+        assert!(system.rate_puzzle(&session, &mut player, &mut puzzle));
+        assert!(player.rating > 1500.0);
+        assert!(puzzle.rating < 1500.0);
+
+        let mut session = PuzzleSession::new(board, vec![mate]).expect("");
+        session.attempt(Move::from_uci("a1a2").unwrap());
+        let mut player = Rating::default();
+        let mut puzzle = Rating::default();
+        assert!(system.rate_puzzle(&session, &mut player, &mut puzzle));
+        assert!(player.rating < 1500.0);
+        assert!(puzzle.rating > 1500.0);
+    }
+
+    #[test]
+    fn test_to_and_from_string() {
+        let r = Rating { rating: 1623.5, deviation: 87.25, volatility: 0.0599 };
+        assert_eq!(r.to_string(), "1623.5/87.25/0.0599");
+        assert_eq!(r.to_string().parse(), Ok(r));
+        assert!("1500/350".parse::<Rating>().is_err());
+        assert!("1500/x/0.06".parse::<Rating>().is_err());
+    }
+}
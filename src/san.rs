@@ -0,0 +1,319 @@
+use std::fmt;
+
+use crate::game::{Board, PieceType};
+use crate::moves::Move;
+use crate::square::Square;
+
+// Standard algebraic notation (SAN), the move format used in PGN files and
+// most books, e.g. "Nf3", "exd5", "O-O" or "e8=Q+".
+
+// Why a SAN string couldn't be turned into a move.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum SanError {
+    // The string isn't written like a SAN move.
+    Invalid,
+    // It is written correctly but no legal move matches it.
+    Illegal,
+    // More than one legal move matches, e.g. "Rd1" when both rooks can go there.
+    Ambiguous,
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::Invalid => write!(f, "Not a valid SAN move."),
+            SanError::Illegal => write!(f, "No legal move matches that SAN."),
+            SanError::Ambiguous => write!(f, "More than one legal move matches that SAN."),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+fn san_piece_char(ptype: PieceType) -> char {
+    match ptype {
+        PieceType::Pawn => 'P',
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        PieceType::King => 'K',
+    }
+}
+
+fn san_piece_type(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+impl Board {
+    // Finds the legal move a SAN string describes. Check and mate markers and
+    // annotations like "!?" are ignored, "0-0" is accepted for castling, and
+    // the "=" before a promotion piece may be left out.
+    pub fn move_from_san(&self, san: &str) -> Result<Move, SanError> {
+        let san = san.trim().trim_end_matches(&['+', '#', '!', '?'][..]);
+        let moves = self.legal_moves();
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let to_x = if san.len() == 3 { 6 } else { 2 };
+            let found: Vec<&Move> = moves.iter().filter(|m| {
+                self.piece_at(m.from).map(|p| p.0) == Some(PieceType::King)
+                    && m.from.x() == 4 && m.to.x() == to_x && m.from.y() == m.to.y()
+            }).collect();
+            return match found.len() {
+                0 => Err(SanError::Illegal),
+                _ => Ok(*found[0]),
+            };
+        }
+
+        let mut chars: Vec<char> = san.chars().collect();
+        let ptype = match chars.first().and_then(|c| san_piece_type(*c)) {
+            Some(p) => {
+                chars.remove(0);
+                p
+            }
+            None => PieceType::Pawn,
+        };
+
+        let mut promotion = None;
+        if ptype == PieceType::Pawn {
+            if let Some(last) = chars.last().and_then(|c| san_piece_type(*c)) {
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+                if last == PieceType::King {
+                    return Err(SanError::Invalid);
+                }
+                promotion = Some(last);
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(SanError::Invalid);
+        }
+        let to_str: String = chars[chars.len() - 2..].iter().collect();
+        let to: Square = to_str.parse().map_err(|_| SanError::Invalid)?;
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in chars[..chars.len() - 2].iter() {
+            match c {
+                'a'..='h' if from_file.is_none() && from_rank.is_none() => from_file = Some(*c as u8 - b'a'),
+                '1'..='8' if from_rank.is_none() => from_rank = Some(b'8' - *c as u8),
+                'x' => {}
+                _ => return Err(SanError::Invalid),
+            }
+        }
+        // A pawn move without a file given is a push, which stays on its file.
+        if ptype == PieceType::Pawn && from_file.is_none() {
+            from_file = Some(to.x());
+        }
+
+        let found: Vec<&Move> = moves.iter().filter(|m| {
+            m.to == to
+                && m.promotion == promotion
+                && self.piece_at(m.from).map(|p| p.0) == Some(ptype)
+                && from_file.is_none_or(|x| m.from.x() == x)
+                && from_rank.is_none_or(|y| m.from.y() == y)
+        }).collect();
+        match found.len() {
+            0 => Err(SanError::Illegal),
+            1 => Ok(*found[0]),
+            _ => Err(SanError::Ambiguous),
+        }
+    }
+}
+
+impl Move {
+    // Writes the move in SAN for the position it is played from, with the
+    // shortest disambiguation that works and a "+" or "#" when it checks or
+    // mates. A move that isn't legal on the board is written without the
+    // check marker, since it can't be played to see whether it checks.
+    pub fn to_san(&self, board: &Board) -> String {
+        let ptype = match board.piece_at(self.from) {
+            None => return String::from("--"),
+            Some((ptype, _)) => ptype,
+        };
+        let legal_moves = board.legal_moves();
+        let mut san = String::new();
+        if ptype == PieceType::King && (self.to.x() as i8 - self.from.x() as i8).abs() == 2 {
+            san.push_str(if self.to.x() > self.from.x() { "O-O" } else { "O-O-O" });
+        } else {
+            let is_capture = board.piece_at(self.to).is_some()
+                || (ptype == PieceType::Pawn && self.from.x() != self.to.x());
+            if ptype == PieceType::Pawn {
+                if is_capture {
+                    san.push((b'a' + self.from.x()) as char);
+                }
+            } else {
+                san.push(san_piece_char(ptype));
+                // Other pieces of the same type that could also go there.
+                let others: Vec<&Move> = legal_moves
+                    .iter()
+                    .filter(|m| {
+                        m.to == self.to
+                            && m.from != self.from
+                            && board.piece_at(m.from).map(|p| p.0) == Some(ptype)
+                    })
+                    .collect();
+                if !others.is_empty() {
+                    let file_unique = others.iter().all(|m| m.from.x() != self.from.x());
+                    let rank_unique = others.iter().all(|m| m.from.y() != self.from.y());
+                    if file_unique {
+                        san.push((b'a' + self.from.x()) as char);
+                    } else if rank_unique {
+                        san.push((b'8' - self.from.y()) as char);
+                    } else {
+                        san.push_str(&self.from.to_string());
+                    }
+                }
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&self.to.to_string());
+            if let Some(promotion) = self.promotion {
+                san.push('=');
+                san.push(san_piece_char(promotion));
+            }
+        }
+
+        if !legal_moves.contains(self) {
+            return san;
+        }
+        let mut after = board.clone();
+        after.make_move_unchecked(*self);
+        if after.is_checkmate() {
+            san.push('#');
+        } else if after.is_check(after.side_to_move()) {
+            san.push('+');
+        }
+        san
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::new_from_fen(String::from(fen)).expect("")
+    }
+
+    fn mv(from: &str, to: &str) -> Move {
+        Move::new(from.parse().unwrap(), to.parse().unwrap())
+    }
+
+    #[test]
+    fn test_simple_moves() {
+        let b = board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(b.move_from_san("e4"), Ok(mv("e2", "e4")));
+        assert_eq!(b.move_from_san("Nf3"), Ok(mv("g1", "f3")));
+        assert_eq!(b.move_from_san("Nf3!?"), Ok(mv("g1", "f3")));
+        assert_eq!(b.move_from_san("e5"), Err(SanError::Illegal));
+        assert_eq!(b.move_from_san("Bc4"), Err(SanError::Illegal));
+        assert_eq!(b.move_from_san("Zf3"), Err(SanError::Invalid));
+        assert_eq!(b.move_from_san(""), Err(SanError::Invalid));
+    }
+
+    #[test]
+    fn test_captures() {
+        let b = board("rnbqkbnr/ppp1pppp/8/3p4/2P1P3/8/PP1P1PPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(b.move_from_san("exd5"), Ok(mv("e4", "d5")));
+        assert_eq!(b.move_from_san("cxd5"), Ok(mv("c4", "d5")));
+        assert_eq!(b.move_from_san("xd5"), Err(SanError::Illegal));
+        assert_eq!(b.move_from_san("d5"), Err(SanError::Illegal));
+        let ep = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        assert_eq!(ep.move_from_san("exd6"), Ok(mv("e5", "d6")));
+    }
+
+    #[test]
+    fn test_disambiguation() {
+        let b = board("3k4/8/8/8/R7/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(b.move_from_san("Rb1"), Ok(mv("a1", "b1")));
+        assert_eq!(b.move_from_san("Ra2"), Err(SanError::Ambiguous));
+        assert_eq!(b.move_from_san("R1a2"), Ok(mv("a1", "a2")));
+        assert_eq!(b.move_from_san("R4a2"), Ok(mv("a4", "a2")));
+        assert_eq!(b.move_from_san("Rf1"), Ok(mv("h1", "f1")));
+        assert_eq!(b.move_from_san("Rad1"), Ok(mv("a1", "d1")));
+        // The king blocks the h1 rook, so only one rook can reach d1.
+        assert_eq!(b.move_from_san("Rd1"), Ok(mv("a1", "d1")));
+        assert_eq!(b.move_from_san("Rhd1"), Err(SanError::Illegal));
+        assert_eq!(b.move_from_san("Ra1d1"), Ok(mv("a1", "d1")));
+    }
+
+    #[test]
+    fn test_castling_and_promotion() {
+        let b = board("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(b.move_from_san("O-O"), Ok(mv("e1", "g1")));
+        assert_eq!(b.move_from_san("O-O-O+"), Ok(mv("e1", "c1")));
+        assert_eq!(b.move_from_san("0-0"), Ok(mv("e1", "g1")));
+        let promote = |from: &str, to: &str, ptype| Move::new_promotion(from.parse().unwrap(), to.parse().unwrap(), ptype);
+        assert_eq!(b.move_from_san("b8=Q"), Ok(promote("b7", "b8", PieceType::Queen)));
+        assert_eq!(b.move_from_san("b8N"), Ok(promote("b7", "b8", PieceType::Knight)));
+        assert_eq!(b.move_from_san("bxa8=R+"), Ok(promote("b7", "a8", PieceType::Rook)));
+        assert_eq!(b.move_from_san("b8"), Err(SanError::Illegal));
+        assert_eq!(b.move_from_san("b8=K"), Err(SanError::Invalid));
+    }
+
+    fn san(fen: &str, from: &str, to: &str) -> String {
+        mv(from, to).to_san(&board(fen))
+    }
+
+    #[test]
+    fn test_to_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "e2", "e4"), "e4");
+        assert_eq!(san(start, "g1", "f3"), "Nf3");
+        assert_eq!(san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1", "e4", "d5"), "exd5");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5", "d6"), "exd6");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1", "g1"), "O-O");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8", "c8"), "O-O-O");
+        // An illegal king two-step is still written, but isn't played out to
+        // look for check.
+        assert_eq!(san(start, "e1", "g1"), "O-O");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a8"), "Ra8+");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1", "b8"), "Rb8");
+    }
+
+    #[test]
+    fn test_to_san_disambiguation() {
+        let fen = "3k4/8/8/8/R7/8/8/R3K2R w KQ - 0 1";
+        assert_eq!(san(fen, "a1", "a2"), "R1a2");
+        assert_eq!(san(fen, "a4", "a2"), "R4a2");
+        assert_eq!(san(fen, "a1", "b1"), "Rb1");
+        assert_eq!(san("3k4/8/8/8/8/8/8/R3K2R w KQ - 0 1", "a1", "b1"), "Rb1");
+        assert_eq!(san("3k4/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1", "c1"), "Rac1");
+        // The a2 queen shares a file with one rival and a rank with another.
+        let queens = "3k4/8/8/8/Q7/8/Q3Q3/6K1 w - - 0 1";
+        assert_eq!(san(queens, "a2", "c4"), "Qa2c4");
+        assert_eq!(san(queens, "e2", "c4"), "Qec4");
+        assert_eq!(san(queens, "a4", "b3"), "Q4b3");
+    }
+
+    #[test]
+    fn test_to_san_checks_and_promotions() {
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1", "a1", "a8"), "Ra8+");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1", "a8"), "Ra8#");
+        let b = board("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        let promote = |to: &str, ptype| {
+            Move::new_promotion("a7".parse().unwrap(), to.parse().unwrap(), ptype).to_san(&b)
+        };
+        assert_eq!(promote("a8", PieceType::Queen), "a8=Q");
+        assert_eq!(promote("b8", PieceType::Knight), "axb8=N");
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let board = board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1");
+        for mv in board.legal_moves() {
+            assert_eq!(board.move_from_san(&mv.to_san(&board)), Ok(mv));
+        }
+    }
+}
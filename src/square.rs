@@ -0,0 +1,295 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bitboard::Bitboard;
+
+// A single square on the board, stored as an index from 0 to 63.
+// The index is y * 8 + x, where x is the file (0 is the a file) and y is the
+// row counted from the top of the board (0 is the 8th rank), the same way
+// the pieces in a FEN are laid out.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    pub fn new(x: u8, y: u8) -> Square {
+        debug_assert!(x < 8 && y < 8);
+        Square(y * 8 + x)
+    }
+
+    pub fn from_index(index: u8) -> Square {
+        debug_assert!(index < 64);
+        Square(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn x(self) -> u8 {
+        self.0 % 8
+    }
+
+    pub fn y(self) -> u8 {
+        self.0 / 8
+    }
+
+    // a1 is dark, so a square is dark when its file and rank are both odd or
+    // both even counting from a1, which is when x + y is odd here.
+    pub fn color(self) -> SquareColor {
+        if (self.x() + self.y()) % 2 == 1 { SquareColor::Dark } else { SquareColor::Light }
+    }
+
+    // Returns the square one step away in the given direction, or None if
+    // that would go off the board.
+    pub fn offset(self, dir: Direction) -> Option<Square> {
+        let (dx, dy) = dir.delta();
+        let x = self.x() as i8 + dx;
+        let y = self.y() as i8 + dy;
+        if !(0..8).contains(&x) || !(0..8).contains(&y) {
+            return None;
+        }
+        Some(Square::new(x as u8, y as u8))
+    }
+
+    // The larger of the file and rank distances (Chebyshev distance).
+    pub fn distance(self, other: Square) -> u8 {
+        let dx = (self.x() as i8 - other.x() as i8).unsigned_abs();
+        let dy = (self.y() as i8 - other.y() as i8).unsigned_abs();
+        dx.max(dy)
+    }
+
+    // The file distance plus the rank distance.
+    pub fn manhattan_distance(self, other: Square) -> u8 {
+        let dx = (self.x() as i8 - other.x() as i8).unsigned_abs();
+        let dy = (self.y() as i8 - other.y() as i8).unsigned_abs();
+        dx + dy
+    }
+
+    // Number of moves a king needs to get from one square to the other on an
+    // empty board. This is the same as distance.
+    pub fn king_distance(self, other: Square) -> u8 {
+        self.distance(other)
+    }
+
+    // Number of moves a knight needs to get from one square to the other on an
+    // empty board, found with a breadth first search.
+    pub fn knight_distance(self, other: Square) -> u8 {
+        const JUMPS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+        let mut dist = [u8::MAX; 64];
+        let mut queue = vec![self];
+        dist[self.index()] = 0;
+        let mut i = 0;
+        while i < queue.len() {
+            let square = queue[i];
+            i += 1;
+            if square == other {
+                break;
+            }
+            for (dx, dy) in JUMPS.iter() {
+                let x = square.x() as i8 + dx;
+                let y = square.y() as i8 + dy;
+                if !(0..8).contains(&x) || !(0..8).contains(&y) {
+                    continue;
+                }
+                let next = Square::new(x as u8, y as u8);
+                if dist[next.index()] == u8::MAX {
+                    dist[next.index()] = dist[square.index()] + 1;
+                    queue.push(next);
+                }
+            }
+        }
+        dist[other.index()]
+    }
+}
+
+// Parses a square in algebraic notation, e.g. "e4".
+impl FromStr for Square {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Square, String> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+            return Err(String::from("Invalid string to turn into square."));
+        }
+        Ok(Square::new(bytes[0] - b'a', b'8' - bytes[1]))
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum SquareColor {
+    Light,
+    Dark,
+}
+
+// The eight directions a queen can move in. North is towards the 8th rank
+// and east is towards the h file.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum Direction {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    // The change in (x, y) from taking one step in this direction.
+    pub fn delta(self) -> (i8, i8) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::NorthEast => (1, -1),
+            Direction::East => (1, 0),
+            Direction::SouthEast => (1, 1),
+            Direction::South => (0, 1),
+            Direction::SouthWest => (-1, 1),
+            Direction::West => (-1, 0),
+            Direction::NorthWest => (-1, -1),
+        }
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::East => Direction::West,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::South => Direction::North,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::West => Direction::East,
+            Direction::NorthWest => Direction::SouthEast,
+        }
+    }
+
+    // Returns the direction to walk from one square to reach another, if the
+    // two squares share a rank, file or diagonal.
+    pub fn between(from: Square, to: Square) -> Option<Direction> {
+        let dx = to.x() as i8 - from.x() as i8;
+        let dy = to.y() as i8 - from.y() as i8;
+        if from == to || (dx != 0 && dy != 0 && dx.abs() != dy.abs()) {
+            return None;
+        }
+        let step = (dx.signum(), dy.signum());
+        Direction::ALL.iter().copied().find(|d| d.delta() == step)
+    }
+}
+
+// All the squares reached by walking from a square in one direction until the
+// edge of the board, not including the starting square.
+pub fn ray(from: Square, dir: Direction) -> Bitboard {
+    let mut result = Bitboard::EMPTY;
+    let mut square = from;
+    while let Some(next) = square.offset(dir) {
+        result.set(next);
+        square = next;
+    }
+    result
+}
+
+// The squares strictly between two squares on the same rank, file or
+// diagonal. Empty if the squares are not lined up or are next to each other.
+pub fn between(from: Square, to: Square) -> Bitboard {
+    match Direction::between(from, to) {
+        None => Bitboard::EMPTY,
+        Some(dir) => ray(from, dir) & ray(to, dir.opposite()),
+    }
+}
+
+// Prints the square in algebraic notation, e.g. "e4".
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.x()) as char, 8 - self.y())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_coordinates() {
+        let square = Square::new(4, 6);
+        assert_eq!(square.index(), 52);
+        assert_eq!(square.x(), 4);
+        assert_eq!(square.y(), 6);
+        assert_eq!(Square::from_index(52), square);
+        assert_eq!(square.to_string(), "e2");
+        assert_eq!(Square::new(0, 0).to_string(), "a8");
+        assert_eq!(Square::new(7, 7).to_string(), "h1");
+    }
+
+    #[test]
+    fn test_parse_square() {
+        assert_eq!("e4".parse::<Square>(), Ok(Square::new(4, 4)));
+        assert_eq!("a8".parse::<Square>(), Ok(Square::new(0, 0)));
+        assert_eq!("h1".parse::<Square>(), Ok(Square::new(7, 7)));
+        assert!("i1".parse::<Square>().is_err());
+        assert!("a0".parse::<Square>().is_err());
+        assert!("a10".parse::<Square>().is_err());
+    }
+
+    #[test]
+    fn test_offset() {
+        assert_eq!(Square::new(4, 4).offset(Direction::North), Some(Square::new(4, 3)));
+        assert_eq!(Square::new(4, 4).offset(Direction::SouthWest), Some(Square::new(3, 5)));
+        assert_eq!(Square::new(0, 0).offset(Direction::North), None);
+        assert_eq!(Square::new(7, 3).offset(Direction::East), None);
+    }
+
+    #[test]
+    fn test_color() {
+        let color = |s: &str| s.parse::<Square>().unwrap().color();
+        assert_eq!(color("a1"), SquareColor::Dark);
+        assert_eq!(color("h1"), SquareColor::Light);
+        assert_eq!(color("a8"), SquareColor::Light);
+        assert_eq!(color("h8"), SquareColor::Dark);
+        assert_eq!(color("d1"), SquareColor::Light);
+        assert_eq!(color("e1"), SquareColor::Dark);
+    }
+
+    #[test]
+    fn test_distances() {
+        let a1 = Square::new(0, 7);
+        let h8 = Square::new(7, 0);
+        let c2 = Square::new(2, 6);
+        assert_eq!(a1.distance(h8), 7);
+        assert_eq!(a1.king_distance(c2), 2);
+        assert_eq!(a1.manhattan_distance(h8), 14);
+        assert_eq!(a1.manhattan_distance(c2), 3);
+        assert_eq!(a1.knight_distance(a1), 0);
+        assert_eq!(a1.knight_distance(c2), 1);
+        assert_eq!(a1.knight_distance(h8), 6);
+        // Corner to the diagonally adjacent square is the slowest short trip.
+        assert_eq!(a1.knight_distance(Square::new(1, 6)), 4);
+    }
+
+    #[test]
+    fn test_ray() {
+        let squares: Vec<Square> = ray(Square::new(5, 2), Direction::NorthEast).iter().collect();
+        assert_eq!(squares, vec![Square::new(7, 0), Square::new(6, 1)]);
+        assert_eq!(ray(Square::new(0, 4), Direction::West), Bitboard::EMPTY);
+        assert_eq!(ray(Square::new(0, 4), Direction::East).count(), 7);
+    }
+
+    #[test]
+    fn test_between() {
+        let squares: Vec<Square> = between(Square::new(0, 7), Square::new(3, 4)).iter().collect();
+        assert_eq!(squares, vec![Square::new(2, 5), Square::new(1, 6)]);
+        assert_eq!(between(Square::new(4, 7), Square::new(4, 0)).count(), 6);
+        assert_eq!(between(Square::new(0, 0), Square::new(1, 2)), Bitboard::EMPTY);
+        assert_eq!(between(Square::new(3, 3), Square::new(4, 3)), Bitboard::EMPTY);
+    }
+}